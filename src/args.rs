@@ -1,16 +1,82 @@
+use crate::stat::{ProcFilter, PidSelector, LeakDetector, EventLog, BitTables, WatchReg, WatchCounter};
+use std::path::Path;
+use std::time::Duration;
+
 #[derive(Default)]
 pub struct MainOpt {
     pub instance: u32,
     pub dump: bool,
     pub json_output: bool,
+    pub regs: Option<u32>, // number of snapshots
+    pub passive: bool,
     pub refresh_period: u64, // ms
-    pub pid: Option<i32>,
+    pub samples: Option<u32>,
+    pub stats_window: Option<u64>, // s
+    pub pid: Option<PidSelector>,
+    pub proc_root: Option<String>,
+    pub filter: Option<ProcFilter>,
+    pub scan_interval: Option<u64>, // ms
+    pub leak_window: Option<u64>, // s
+    pub event_log: Option<String>,
+    pub bit_table: Option<String>,
+    pub watch_reg: Vec<WatchReg>,
 }
 
+/// default number of register reads per refresh period
+pub const SAMPLES: u32 = 100;
+pub const MAX_SAMPLES: u32 = 10_000;
+
+/// default window of the perf counter statistics, seconds
+const STATS_WINDOW: u64 = 60;
+const MAX_STATS_WINDOW: u64 = 3600;
+
+/// default interval of scanning processes using the device
+const SCAN_INTERVAL_MS: u64 = 5000;
+
 impl MainOpt {
     pub fn device_path(&self) -> String {
         format!("/dev/dri/renderD{}", 128 + self.instance)
     }
+
+    pub fn leak_detector(&self) -> Option<LeakDetector> {
+        self.leak_window.map(|secs| LeakDetector::new(Duration::from_secs(secs)))
+    }
+
+    pub fn event_log(&self) -> EventLog {
+        let Some(path) = &self.event_log else { return EventLog::default() };
+
+        EventLog::with_file(Path::new(path)).unwrap_or_else(|err| {
+            eprintln!("Failed to open {path}: {err}");
+            std::process::exit(1);
+        })
+    }
+
+    pub fn bit_tables(&self) -> BitTables {
+        BitTables::with_override(self.bit_table.as_deref().map(Path::new)).unwrap_or_else(|err| {
+            eprintln!("Invalid bit table: {err}");
+            std::process::exit(1);
+        })
+    }
+
+    /// registers of the "watch" section of the bit table file, then `--watch-reg`
+    pub fn watch_counter(&self, bit_tables: &BitTables) -> WatchCounter {
+        let regs = bit_tables.watch.iter().chain(self.watch_reg.iter()).cloned().collect();
+
+        WatchCounter::new(regs, self.stats_window())
+    }
+
+    /// number of register reads per refresh period
+    pub fn sample_count(&self) -> u32 {
+        self.samples.unwrap_or(SAMPLES)
+    }
+
+    pub fn stats_window(&self) -> Duration {
+        Duration::from_secs(self.stats_window.unwrap_or(STATS_WINDOW))
+    }
+
+    pub fn scan_interval(&self) -> Duration {
+        Duration::from_millis(self.scan_interval.unwrap_or(SCAN_INTERVAL_MS))
+    }
 }
 
 const HELP_MSG: &str = concat!(
@@ -25,14 +91,42 @@ const HELP_MSG: &str = concat!(
     "       Dump AMDGPU info (name, clock, chip_class, VRAM, PCI, VBIOS)\n",
     "   -J\n",
     "       Output JSON formatted data for simple process trace\n",
+    "   --regs [<u32>]\n",
+    "       Read the status registers once (or <u32> times, every `-s` ms, default: 100ms),\n",
+    "       and print their raw values with the fields decoded, in JSON with `-J`\n",
+    "   --passive\n",
+    "       Never read MMIO registers (GRBM, CP_STAT, ..), utilization is taken from\n",
+    "       fdinfo, the GPU_LOAD sensor, gpu_busy_percent and gpu_metrics instead\n",
     "   -s <i64>, --ms <i64>\n",
-    "       Refresh period in milliseconds for simple process trace\n",
+    "       Refresh period in milliseconds (default: 1000 for TUI, no delay for `-J`)\n",
     "   -p <i32>, --pid <i32>\n",
-    "       Specification of PID, used for `-J` option\n",
+    "       Specification of PID, used for `-J` option (default: all processes using the GPU)\n",
+    "       \"<PID namespace>:<PID>\" selects a PID inside the namespace (e.g. \"4026532451:42\")\n",
     "\n",
     "OPTIONS:\n",
     "   -i <u32>\n",
     "       Select GPU instance\n",
+    "   --samples <u32>\n",
+    "       Number of register reads per refresh period (default: 100, max: 10000),\n",
+    "       the delay between reads is <refresh period> / <samples>\n",
+    "   --stats-window <u64>\n",
+    "       Window in seconds of min/avg/max/p95 of the perf counters (default: 60, max: 3600)\n",
+    "   --filter <filter>\n",
+    "       Filter processes of fdinfo by name pattern, \"pid:<PID>\" or \"user:<name|UID>\"\n",
+    "   --proc-root <path>\n",
+    "       Read processes from <path> instead of /proc\n",
+    "   --scan-interval <u64>\n",
+    "       Interval in milliseconds of scanning processes using the GPU (default: 5000)\n",
+    "   --leak-window <u64>\n",
+    "       Flag processes whose VRAM+GTT usage rises steadily over <u64> seconds\n",
+    "   --event-log <file>\n",
+    "       Append events of processes starting/stopping to use the GPU to <file> (JSON lines)\n",
+    "   --bit-table <file>\n",
+    "       Load register bit tables from <file> (JSON), in preference to the built-in tables\n",
+    "   --watch-reg <name>:<offset>:<bit>[:<width>]\n",
+    "       Watch a MMIO status field, can be repeated (e.g. \"GUI_ACTIVE:0x2004:31\")\n",
+    "       Multi-bit fields (<width> up to 4) are shown as value histograms\n",
+    "       Also read from the \"watch\" list of the `--bit-table` file\n",
 );
 
 impl MainOpt {
@@ -69,6 +163,23 @@ impl MainOpt {
                 "-J" => {
                     opt.json_output = true;
                 },
+                "--passive" => {
+                    opt.passive = true;
+                },
+                "--regs" => {
+                    // the number of snapshots is optional
+                    opt.regs = match args.get(idx+1).and_then(|val_str| val_str.parse::<u32>().ok()) {
+                        Some(0) => {
+                            eprintln!("invalid argument: \"--regs [<u32>]\" (> 0)");
+                            std::process::exit(1);
+                        },
+                        Some(count) => {
+                            skip = true;
+                            Some(count)
+                        },
+                        None => Some(1),
+                    };
+                },
                 "-s" | "--ms" => {
                     if let Some(val_str) = args.get(idx+1) {
                         opt.refresh_period = val_str.parse::<u64>().unwrap();
@@ -78,15 +189,138 @@ impl MainOpt {
                         std::process::exit(1);
                     }
                 },
+                "--samples" => {
+                    if let Some(val_str) = args.get(idx+1) {
+                        match val_str.parse::<u32>() {
+                            Ok(n) if (1..=MAX_SAMPLES).contains(&n) => opt.samples = Some(n),
+                            _ => {
+                                eprintln!("invalid argument: \"--samples <u32>\" (1-{MAX_SAMPLES})");
+                                std::process::exit(1);
+                            },
+                        }
+                        skip = true;
+                    } else {
+                        eprintln!("missing argument: \"--samples <u32>\"");
+                        std::process::exit(1);
+                    }
+                },
+                "--stats-window" => {
+                    if let Some(val_str) = args.get(idx+1) {
+                        match val_str.parse::<u64>() {
+                            Ok(n) if (1..=MAX_STATS_WINDOW).contains(&n) => opt.stats_window = Some(n),
+                            _ => {
+                                eprintln!("invalid argument: \"--stats-window <u64>\" (1-{MAX_STATS_WINDOW})");
+                                std::process::exit(1);
+                            },
+                        }
+                        skip = true;
+                    } else {
+                        eprintln!("missing argument: \"--stats-window <u64>\"");
+                        std::process::exit(1);
+                    }
+                },
                 "-p" | "--pid" => {
                     if let Some(val_str) = args.get(idx+1) {
-                        opt.pid = Some(val_str.parse::<i32>().unwrap());
+                        match val_str.parse::<PidSelector>() {
+                            Ok(pid) => opt.pid = Some(pid),
+                            Err(err) => {
+                                eprintln!("{err}");
+                                std::process::exit(1);
+                            },
+                        }
                         skip = true;
                     } else {
                         eprintln!("missing argument: \"-p <i32>\"");
                         std::process::exit(1);
                     }
                 },
+                "--filter" => {
+                    if let Some(val_str) = args.get(idx+1) {
+                        match val_str.parse::<ProcFilter>() {
+                            Ok(filter) => opt.filter = Some(filter),
+                            Err(err) => {
+                                eprintln!("{err}");
+                                std::process::exit(1);
+                            },
+                        }
+                        skip = true;
+                    } else {
+                        eprintln!("missing argument: \"--filter <filter>\"");
+                        std::process::exit(1);
+                    }
+                },
+                "--proc-root" => {
+                    if let Some(val_str) = args.get(idx+1) {
+                        opt.proc_root = Some(val_str.to_string());
+                        skip = true;
+                    } else {
+                        eprintln!("missing argument: \"--proc-root <path>\"");
+                        std::process::exit(1);
+                    }
+                },
+                "--scan-interval" => {
+                    if let Some(val_str) = args.get(idx+1) {
+                        match val_str.parse::<u64>() {
+                            Ok(n) if 0 < n => opt.scan_interval = Some(n),
+                            _ => {
+                                eprintln!("invalid argument: \"--scan-interval <u64>\" (> 0)");
+                                std::process::exit(1);
+                            },
+                        }
+                        skip = true;
+                    } else {
+                        eprintln!("missing argument: \"--scan-interval <u64>\"");
+                        std::process::exit(1);
+                    }
+                },
+                "--leak-window" => {
+                    if let Some(val_str) = args.get(idx+1) {
+                        match val_str.parse::<u64>() {
+                            Ok(n) if 0 < n => opt.leak_window = Some(n),
+                            _ => {
+                                eprintln!("invalid argument: \"--leak-window <u64>\" (> 0)");
+                                std::process::exit(1);
+                            },
+                        }
+                        skip = true;
+                    } else {
+                        eprintln!("missing argument: \"--leak-window <u64>\"");
+                        std::process::exit(1);
+                    }
+                },
+                "--event-log" => {
+                    if let Some(val_str) = args.get(idx+1) {
+                        opt.event_log = Some(val_str.to_string());
+                        skip = true;
+                    } else {
+                        eprintln!("missing argument: \"--event-log <file>\"");
+                        std::process::exit(1);
+                    }
+                },
+                "--bit-table" => {
+                    if let Some(val_str) = args.get(idx+1) {
+                        opt.bit_table = Some(val_str.to_string());
+                        skip = true;
+                    } else {
+                        eprintln!("missing argument: \"--bit-table <file>\"");
+                        std::process::exit(1);
+                    }
+                },
+                "--watch-reg" => {
+                    if let Some(val_str) = args.get(idx+1) {
+                        match val_str.parse::<WatchReg>() {
+                            Ok(reg) => opt.watch_reg.push(reg),
+                            Err(err) => {
+                                eprintln!("{err}");
+                                std::process::exit(1);
+                            },
+                        }
+                        skip = true;
+                    } else {
+                        eprintln!("missing argument: \"--watch-reg <name>:<offset>:<bit>[:<width>]\"");
+                        std::process::exit(1);
+                    }
+                },
                 "-h" | "--help" => {
                     println!("{HELP_MSG}");
                     std::process::exit(0);
@@ -97,6 +331,11 @@ impl MainOpt {
             }
         }
 
+        if opt.passive && opt.regs.is_some() {
+            eprintln!("\"--regs\" reads registers, cannot be used with \"--passive\"");
+            std::process::exit(1);
+        }
+
         opt
     }
 }
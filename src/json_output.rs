@@ -1,21 +1,25 @@
-use libdrm_amdgpu_sys::AMDGPU::{DeviceHandle, CHIP_CLASS, GPU_INFO};
+use libdrm_amdgpu_sys::AMDGPU::{DeviceHandle, GPU_INFO};
 use crate::stat;
+use crate::args::MainOpt;
 use std::time::{Duration, Instant};
 use std::io::{self, stdin, Read, BufReader};
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
+use std::path::Path;
 use ctrlc;
 use serde_json::{json, Value};
 
 pub fn print(
     amdgpu_dev: &DeviceHandle,
     device_path: &str,
-    refresh_period: u64,
-    pid: i32
+    proc_root: &Path,
+    pid: Option<i32>,
+    main_opt: &MainOpt,
 ) -> io::Result<()> {
     let ext_info = amdgpu_dev.device_info().unwrap();
     let memory_info = amdgpu_dev.memory_info().unwrap();
     let chip_class = ext_info.get_chip_class();
+    let asic_name = ext_info.get_asic_name();
     let pci_bus = amdgpu_dev.get_pci_bus_info().unwrap();
     let mark_name = amdgpu_dev.get_marketing_name().unwrap_or("".to_string());
     let cu_count = ext_info.cu_active_number();
@@ -23,23 +27,63 @@ pub fn print(
     /* ref: https://gitlab.freedesktop.org/mesa/mesa/blob/main/src/amd/common/ac_gpu_info.c */
     let resizable_bar = (memory_info.vram.total_heap_size * 9 / 10) <= memory_info.cpu_accessible_vram.total_heap_size;
 
-    let grbm_index = if CHIP_CLASS::GFX10 <= chip_class {
-        stat::GFX10_GRBM_INDEX
-    } else {
-        stat::GRBM_INDEX
+    let bit_tables = main_opt.bit_tables();
+    let stats_window = main_opt.stats_window();
+    let perf_counter = |pc_type: stat::PCType| {
+        let index = bit_tables.index(&pc_type, &chip_class.to_string(), &asic_name.to_string());
+
+        stat::PerfCounter::new(pc_type, index, stats_window)
     };
 
-    let mut grbm = stat::PerfCounter::new(stat::PCType::GRBM, grbm_index);
-    let mut grbm2 = stat::PerfCounter::new(stat::PCType::GRBM2, stat::GRBM2_INDEX);
-    let mut cp_stat = stat::PerfCounter::new(stat::PCType::CP_STAT, stat::CP_STAT_INDEX);
+    let mut grbm = perf_counter(stat::PCType::GRBM);
+    let mut grbm2 = perf_counter(stat::PCType::GRBM2);
+    let mut grbm_se = stat::GrbmSeView::new(
+        ext_info.max_se(),
+        bit_tables.index_by_name(stat::GrbmSeView::TITLE, &chip_class.to_string(), &asic_name.to_string()),
+        stats_window,
+    );
+    let mut srbm = perf_counter(stat::PCType::SRBM);
+    let mut srbm2 = perf_counter(stat::PCType::SRBM2);
+    let mut cp_stat = perf_counter(stat::PCType::CP_STAT);
+    let mut watch_reg = main_opt.watch_counter(&bit_tables);
+    // no register is read in passive mode
+    let regs = !main_opt.passive;
+    let grbm_se_readable = regs && grbm_se.is_readable(amdgpu_dev);
+    if regs {
+        watch_reg.retain_readable(amdgpu_dev);
+    } else {
+        watch_reg = stat::WatchCounter::default();
+    }
+    // SRBM registers are not readable on GFX9 and later
+    let srbm_readable = regs && !srbm.index.is_empty() && srbm.pc_type.is_readable(amdgpu_dev);
+    let srbm2_readable = regs && !srbm2.index.is_empty() && srbm2.pc_type.is_readable(amdgpu_dev);
+    let mut activity = main_opt.passive.then(|| stat::ActivityView::new(amdgpu_dev, stats_window));
     let mut vram = stat::VramUsageView::new(&memory_info);
 
-    let mut period = Duration::from_millis(refresh_period);
-    let delay = period / 100;
-
-    let proc_info = stat::ProcInfo::from_pid(pid, device_path);
-    let mut fdinfo = stat::FdInfoView::new(period);
-    fdinfo.get_proc_usage(&proc_info);
+    let mut period = Duration::from_millis(main_opt.refresh_period);
+    let samples = main_opt.sample_count();
+    let delay = period / samples;
+
+    let device_nodes = stat::get_drm_nodes(device_path);
+    let self_pid = std::process::id() as i32;
+    // trace all processes using the device if PID is not specified
+    let scan_interval = main_opt.scan_interval();
+    let mut proc_index = stat::ProcIndex::new(proc_root, &device_nodes, self_pid);
+    proc_index.kfd = stat::KfdCollector::new(device_path);
+    match pid {
+        Some(pid) => proc_index.procs = vec![stat::ProcInfo::from_pid(pid, proc_root, &device_nodes)],
+        None => proc_index.update(),
+    }
+    let mut last_scan = Instant::now();
+    let mut fdinfo = stat::FdInfoView::new(proc_root);
+    fdinfo.kfd = proc_index.kfd.clone();
+    fdinfo.leak = main_opt.leak_detector();
+    let mut event_log = main_opt.event_log();
+    let mut engines = stat::EnginesView::new(stats_window);
+    fdinfo.filter = main_opt.filter.clone();
+    for proc_info in &proc_index.procs {
+        fdinfo.get_proc_usage(proc_info);
+    }
 
     let mut sensor = stat::Sensor::new(&pci_bus);
 
@@ -74,18 +118,65 @@ pub fn print(
     let base = Instant::now();
 
     loop {
-        for _ in 0..100 {
-            grbm.read_reg(amdgpu_dev);
-            grbm2.read_reg(amdgpu_dev);
-            cp_stat.read_reg(amdgpu_dev);
+        for _ in 0..samples {
+            if regs {
+                grbm.read_reg(amdgpu_dev);
+                grbm2.read_reg(amdgpu_dev);
+                cp_stat.read_reg(amdgpu_dev);
+            }
+            if grbm_se_readable {
+                grbm_se.read_reg(amdgpu_dev);
+            }
+            if srbm_readable {
+                srbm.read_reg(amdgpu_dev);
+            }
+            if srbm2_readable {
+                srbm2.read_reg(amdgpu_dev);
+            }
+            watch_reg.read_reg(amdgpu_dev);
 
             std::thread::sleep(delay);
         }
 
+        // e.g. the container of "-p <ns>:<pid>" has stopped
+        let exited = pid.and_then(|_| proc_index.procs.iter().find(|p| !p.is_alive(proc_root)));
+        let target_exited = exited.is_some();
+        if let Some(proc_info) = exited {
+            eprintln!("Error: the process has exited: {} ({})", proc_info.name, proc_info.pid);
+            quit_flag.store(true, Ordering::Relaxed);
+        }
+
         vram.update_usage(amdgpu_dev);
+        if let Some(activity) = &mut activity {
+            activity.update(amdgpu_dev);
+        }
         sensor.update_status();
-        fdinfo.proc_usage.clear();
-        fdinfo.get_proc_usage(&proc_info);
+        fdinfo.clear_usage();
+        if pid.is_none() && scan_interval <= last_scan.elapsed() {
+            proc_index.update();
+            last_scan = Instant::now();
+        }
+        for proc_info in &proc_index.procs {
+            fdinfo.get_proc_usage(proc_info);
+        }
+        fdinfo.update_exited(&proc_index.procs);
+        engines.usage = fdinfo.engines();
+        event_log.update(&proc_index.procs, &fdinfo.exited);
+        if let Some(err) = event_log.take_write_error() {
+            eprintln!("{err}");
+        }
+
+        grbm.update_stats();
+        grbm2.update_stats();
+        grbm_se.update_stats();
+        srbm.update_stats();
+        srbm2.update_stats();
+        cp_stat.update_stats();
+        watch_reg.update_stats();
+        engines.update_stats();
+        if let Some(activity) = &mut activity {
+            activity.update_stats();
+        }
 
         let now = Instant::now();
         period = now.duration_since(base);
@@ -98,21 +189,39 @@ pub fn print(
                 "duration": period.as_millis(),
                 "unit": "ms",
             },
-            "GRBM": grbm.json_value(),
-            "GRBM2": grbm2.json_value(),
-            "CP_STAT": cp_stat.json_value(),
+            "GRBM": if regs { grbm.json_value() } else { Value::Null },
+            "GRBM2": if regs { grbm2.json_value() } else { Value::Null },
+            "GRBM_STATUS_SE": if grbm_se_readable { grbm_se.json_value() } else { Value::Null },
+            "SRBM": if srbm_readable { srbm.json_value() } else { Value::Null },
+            "SRBM2": if srbm2_readable { srbm2.json_value() } else { Value::Null },
+            "CP_STAT": if regs { cp_stat.json_value() } else { Value::Null },
+            "Watch": if watch_reg.is_empty() { Value::Null } else { watch_reg.json_value() },
+            "Activity": activity.as_ref().map(|activity| activity.json_value()),
             "VRAM": vram.json_value(),
+            "Engines": engines.json_value(),
             "fdinfo": fdinfo.json_value(),
+            "processes": fdinfo.json_all_value(),
+            "cgroups": fdinfo.json_group_value(&stat::FdInfoGroupType::Cgroup),
+            "users": fdinfo.json_group_value(&stat::FdInfoGroupType::User),
             "Sensors": sensor.json_value(amdgpu_dev),
         });
 
         grbm.bits.clear();
         grbm2.bits.clear();
+        grbm_se.clear();
+        srbm.bits.clear();
+        srbm2.bits.clear();
         cp_stat.bits.clear();
+        watch_reg.clear();
 
-        vec_value.push(json);
+        if !target_exited {
+            vec_value.push(json);
+        }
 
         if quit_flag.load(Ordering::Relaxed) {
+            vec_value.push(json!({
+                "ProcessLedger": fdinfo.json_ledger_value(),
+            }));
             println!("{}", vec_value.into_iter().collect::<Value>());
             return Ok(());
         }
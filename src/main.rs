@@ -1,29 +1,49 @@
-use libdrm_amdgpu_sys::AMDGPU::{DeviceHandle, CHIP_CLASS, GPU_INFO};
+use libdrm_amdgpu_sys::AMDGPU::{DeviceHandle, GPU_INFO};
 use std::sync::{Arc, Mutex};
 use std::time::Duration;
-use cursive::views::{TextView, LinearLayout, Panel};
+use std::path::{Path, PathBuf};
+use cursive::views::{TextContent, TextView, LinearLayout, Panel};
 use cursive::view::Scrollable;
 use cursive::align::HAlign;
+use cursive::event::Event;
 
 mod stat;
 mod args;
 mod misc;
 mod dump_info;
 mod json_output;
+mod reg_snapshot;
 
-use stat::FdInfoSortType;
+use stat::{FdInfoSortType, FdInfoGroupType, ProcFilter};
 
 #[derive(Debug, Clone)]
 struct ToggleOptions {
     grbm: bool,
     grbm2: bool,
+    grbm_se: bool,
+    srbm: bool,
+    srbm2: bool,
     cp_stat: bool,
+    watch_reg: bool,
     vram: bool,
     sensor: bool,
     high_freq: bool,
+    sample_count: u32,
+    refresh_period: u64, // ms
     fdinfo: bool,
+    engines: bool,
+    activity: bool,
     fdinfo_sort: FdInfoSortType,
     reverse_sort: bool,
+    fdinfo_clients: bool,
+    fdinfo_group: FdInfoGroupType,
+    fdinfo_filter: Option<ProcFilter>,
+    /// PID of the selected row
+    fdinfo_selected: Option<i32>,
+    /// PID and user name of the rows, updated by the fdinfo panel
+    fdinfo_rows: Vec<(i32, String)>,
+    fdinfo_ledger: bool,
+    event_log: bool,
     gpu_metrics: bool,
 }
 
@@ -32,13 +52,28 @@ impl Default for ToggleOptions {
         Self {
             grbm: true,
             grbm2: true,
+            grbm_se: true,
+            srbm: true,
+            srbm2: true,
             cp_stat: true,
+            watch_reg: true,
             vram: true,
             sensor: true,
             high_freq: false,
+            sample_count: args::SAMPLES,
+            refresh_period: 1000,
             fdinfo: true,
+            engines: true,
+            activity: true,
             fdinfo_sort: FdInfoSortType::VRAM,
             reverse_sort: false,
+            fdinfo_clients: false,
+            fdinfo_group: FdInfoGroupType::Process,
+            fdinfo_filter: None,
+            fdinfo_selected: None,
+            fdinfo_rows: Vec::new(),
+            fdinfo_ledger: true,
+            event_log: true,
             gpu_metrics: false,
         }
     }
@@ -47,16 +82,21 @@ impl Default for ToggleOptions {
 type Opt = Arc<Mutex<ToggleOptions>>;
 
 const TOGGLE_HELP: &str = concat!(
-    " (g)rbm g(r)bm2 (c)p_stat (v)ram_usage (f)dinfo \n",
-    " se(n)sor (m)etrics (h)igh_freq (q)uit \n",
-    " (P): sort_by_pid (V): sort_by_vram (G): sort_by_gfx\n (M): sort_by_media (R): reverse"
+    " (g)rbm g(r)bm2 grbm_(S)e srbm(u) (s)rbm2 (c)p_stat (w)atch (a)ctivity \n",
+    " (v)ram_usage (f)dinfo (E)ngines se(n)sor (m)etrics (h)igh_freq (q)uit \n",
+    " (+/-): samples ([/]): refresh_period\n",
+    " (P): sort_by_pid (V): sort_by_vram (G): sort_by_gfx\n (M): sort_by_media (R): reverse\n",
+    " (e): expand_clients (C): group_by_cgroup (U): group_by_user (/): filter\n",
+    " (x): process_gpu_time (d): process_detail (k): send_signal (l): event_log\n",
+    " (^N/^P): select_fdinfo_row"
 );
 
 fn main() {
     let main_opt = args::MainOpt::parse();
     let device_path = main_opt.device_path();
 
-    let self_pid = stat::get_self_pid().unwrap_or(0);
+    let proc_root = PathBuf::from(main_opt.proc_root.as_deref().unwrap_or(stat::PROC_ROOT));
+    let self_pid = std::process::id() as i32;
 
     let (amdgpu_dev, major, minor) = {
         use std::fs::File;
@@ -72,17 +112,25 @@ fn main() {
         return;
     }
 
+    if let Some(count) = main_opt.regs {
+        reg_snapshot::print(&amdgpu_dev, &main_opt, count);
+        return;
+    }
+
     if main_opt.json_output {
-        let Some(self_pid) = main_opt.pid else {
-            eprintln!("PID is not specified.");
-            return;
-        };
+        let pid = main_opt.pid.as_ref().map(|sel| {
+            sel.resolve(&proc_root).unwrap_or_else(|| {
+                eprintln!("Error: PID not found: {sel}");
+                std::process::exit(1);
+            })
+        });
 
         if let Err(err) = json_output::print(
             &amdgpu_dev,
             &device_path,
-            main_opt.refresh_period,
-            self_pid
+            &proc_root,
+            pid,
+            &main_opt,
         ) {
             eprintln!("Error: {err}");
         }
@@ -93,6 +141,7 @@ fn main() {
     let memory_info = amdgpu_dev.memory_info().unwrap();
     let pci_bus = amdgpu_dev.get_pci_bus_info().unwrap();
     let chip_class = ext_info.get_chip_class();
+    let asic_name = ext_info.get_asic_name();
 
     let (min_gpu_clk, min_memory_clk) = misc::get_min_clk(&amdgpu_dev, &pci_bus);
     let mark_name = amdgpu_dev.get_marketing_name().unwrap_or("".to_string());
@@ -106,7 +155,7 @@ fn main() {
         mark_name = mark_name,
         did = ext_info.device_id(),
         rid = ext_info.pci_rev_id(),
-        asic = ext_info.get_asic_name(),
+        asic = asic_name,
         gpu_type = if ext_info.is_apu() { "APU" } else { "dGPU" },
         chip_class = chip_class,
         num_cu = ext_info.cu_active_number(),
@@ -119,30 +168,74 @@ fn main() {
         max_memory_clk = ext_info.max_memory_clock().saturating_div(1000),
     );
 
-    let grbm_index = if CHIP_CLASS::GFX10 <= chip_class {
-        stat::GFX10_GRBM_INDEX
-    } else {
-        stat::GRBM_INDEX
+    let bit_tables = main_opt.bit_tables();
+    let stats_window = main_opt.stats_window();
+    let perf_counter = |pc_type: stat::PCType| {
+        let index = bit_tables.index(&pc_type, &chip_class.to_string(), &asic_name.to_string());
+
+        stat::PerfCounter::new(pc_type, index, stats_window)
     };
 
-    let mut grbm = stat::PerfCounter::new(stat::PCType::GRBM, grbm_index);
-    let mut grbm2 = stat::PerfCounter::new(stat::PCType::GRBM2, stat::GRBM2_INDEX);
-    let mut cp_stat = stat::PerfCounter::new(stat::PCType::CP_STAT, stat::CP_STAT_INDEX);
+    let mut grbm = perf_counter(stat::PCType::GRBM);
+    let mut grbm2 = perf_counter(stat::PCType::GRBM2);
+    let mut grbm_se = stat::GrbmSeView::new(
+        ext_info.max_se(),
+        bit_tables.index_by_name(stat::GrbmSeView::TITLE, &chip_class.to_string(), &asic_name.to_string()),
+        stats_window,
+    );
+    let mut srbm = perf_counter(stat::PCType::SRBM);
+    let mut srbm2 = perf_counter(stat::PCType::SRBM2);
+    let mut cp_stat = perf_counter(stat::PCType::CP_STAT);
+    let mut watch_reg = main_opt.watch_counter(&bit_tables);
     let mut vram_usage = stat::VramUsageView::new(&memory_info);
 
-    let mut proc_index: Vec<stat::ProcInfo> = Vec::new();
-    let mut sample = Sampling::low();
-    let mut fdinfo = stat::FdInfoView::new(sample.to_duration());
+    let device_nodes = stat::get_drm_nodes(&device_path);
+    let mut proc_index = stat::ProcIndex::new(&proc_root, &device_nodes, self_pid);
+    proc_index.kfd = stat::KfdCollector::new(&device_path);
+    let mut fdinfo = stat::FdInfoView::new(&proc_root);
+    fdinfo.kfd = proc_index.kfd.clone();
+    fdinfo.leak = main_opt.leak_detector();
+    let mut engines = stat::EnginesView::new(stats_window);
+    let mut event_log = main_opt.event_log();
     let mut sensor = stat::Sensor::new(&pci_bus);
     let mut metrics = stat::GpuMetricsView::new(&amdgpu_dev);
+    // replaces the register panels in passive mode
+    let mut activity = main_opt.passive.then(|| stat::ActivityView::new(&amdgpu_dev, stats_window));
 
-    let mut toggle_opt = ToggleOptions::default();
-
-    {   // check register offset
-        toggle_opt.grbm = grbm.pc_type.check_reg_offset(&amdgpu_dev);
-        toggle_opt.grbm2 = grbm2.pc_type.check_reg_offset(&amdgpu_dev);
+    let mut toggle_opt = ToggleOptions {
+        fdinfo_filter: main_opt.filter.clone(),
+        sample_count: main_opt.sample_count(),
+        ..Default::default()
+    };
+    if main_opt.refresh_period != 0 {
+        toggle_opt.refresh_period = main_opt.refresh_period;
+    }
+    let mut sample = Sampling::from_opt(&toggle_opt);
+    let sampling_info = TextContent::new(sample.to_string());
+    fdinfo.filter = toggle_opt.fdinfo_filter.clone();
+
+    if main_opt.passive {
+        toggle_opt.grbm = false;
+        toggle_opt.grbm2 = false;
+        toggle_opt.grbm_se = false;
+        toggle_opt.srbm = false;
+        toggle_opt.srbm2 = false;
+        toggle_opt.cp_stat = false;
+        toggle_opt.watch_reg = false;
+    } else {
+        // check register offset
+        // registers without bits for the device are not shown
+        toggle_opt.grbm = !grbm.index.is_empty() && grbm.pc_type.check_reg_offset(&amdgpu_dev);
+        toggle_opt.grbm2 = !grbm2.index.is_empty() && grbm2.pc_type.check_reg_offset(&amdgpu_dev);
+        toggle_opt.grbm_se = grbm_se.check_reg_offset(&amdgpu_dev);
+        toggle_opt.srbm = !srbm.index.is_empty() && srbm.pc_type.check_reg_offset(&amdgpu_dev);
+        toggle_opt.srbm2 = !srbm2.index.is_empty() && srbm2.pc_type.check_reg_offset(&amdgpu_dev);
         [toggle_opt.cp_stat, _] = [false, cp_stat.pc_type.check_reg_offset(&amdgpu_dev)];
+        watch_reg.check_reg_offset(&amdgpu_dev);
+        toggle_opt.watch_reg = !watch_reg.is_empty();
+    }
 
+    {
         if metrics.update_metrics(&amdgpu_dev).is_ok() {
             toggle_opt.gpu_metrics = true;
             metrics.print().unwrap();
@@ -153,9 +246,21 @@ fn main() {
 
         // fill
         {
-            stat::update_index(&mut proc_index, &device_path, self_pid);
-            fdinfo.print(&proc_index, &toggle_opt.fdinfo_sort, false).unwrap();
+            proc_index.update();
+            fdinfo.print(
+                &proc_index.procs,
+                &toggle_opt.fdinfo_sort,
+                false,
+                false,
+                &toggle_opt.fdinfo_group,
+            ).unwrap();
             fdinfo.text.set();
+            toggle_opt.fdinfo_rows = fdinfo.rows.clone();
+            engines.usage = fdinfo.engines();
+            engines.set_value();
+            event_log.update(&proc_index.procs, &fdinfo.exited);
+            event_log.print().unwrap();
+            event_log.text.set();
         }
         {
             sensor.print(&amdgpu_dev).unwrap();
@@ -168,12 +273,21 @@ fn main() {
         let mut layout = LinearLayout::vertical()
             .child(
                 Panel::new(
-                    TextView::new(info_bar).center()
+                    TextView::new(if main_opt.passive {
+                        format!("{info_bar}\n[passive mode: no register reads]")
+                    } else {
+                        info_bar
+                    }).center()
                 )
                 .title(concat!(env!("CARGO_PKG_NAME"), " v", env!("CARGO_PKG_VERSION")))
                 .title_position(HAlign::Center)
             );
 
+        if let Some(activity) = &activity {
+            activity.set_value();
+            layout.add_child(activity.top_view(toggle_opt.activity));
+            siv.add_global_callback('a', stat::ActivityView::cb);
+        }
         if toggle_opt.grbm {
             layout.add_child(grbm.top_view(toggle_opt.grbm));
             siv.add_global_callback('g', grbm.pc_type.cb());
@@ -182,14 +296,35 @@ fn main() {
             layout.add_child(grbm2.top_view(toggle_opt.grbm2));
             siv.add_global_callback('r', grbm2.pc_type.cb());
         }
-        {
+        if toggle_opt.grbm_se {
+            layout.add_child(grbm_se.top_view(toggle_opt.grbm_se));
+            siv.add_global_callback('S', stat::GrbmSeView::cb);
+        }
+        if toggle_opt.srbm {
+            layout.add_child(srbm.top_view(toggle_opt.srbm));
+            siv.add_global_callback('u', srbm.pc_type.cb());
+        }
+        if toggle_opt.srbm2 {
+            layout.add_child(srbm2.top_view(toggle_opt.srbm2));
+            siv.add_global_callback('s', srbm2.pc_type.cb());
+        }
+        // the hidden CP_STAT panel can be toggled on, not in passive mode
+        if !main_opt.passive {
             layout.add_child(cp_stat.top_view(toggle_opt.cp_stat));
             siv.add_global_callback('c', cp_stat.pc_type.cb());
         }
+        if toggle_opt.watch_reg {
+            layout.add_child(watch_reg.top_view(toggle_opt.watch_reg));
+            siv.add_global_callback('w', stat::WatchCounter::cb);
+        }
         {
             layout.add_child(vram_usage.view());
             siv.add_global_callback('v', stat::VramUsageView::cb);
         }
+        {
+            layout.add_child(engines.top_view(toggle_opt.engines));
+            siv.add_global_callback('E', stat::EnginesView::cb);
+        }
         {
             layout.add_child(fdinfo.text.panel("fdinfo"));
             siv.add_global_callback('f', stat::FdInfoView::cb);
@@ -198,6 +333,20 @@ fn main() {
             siv.add_global_callback('V', stat::FdInfoView::cb_sort_by_vram);
             siv.add_global_callback('G', stat::FdInfoView::cb_sort_by_gfx);
             siv.add_global_callback('M', stat::FdInfoView::cb_sort_by_media);
+            siv.add_global_callback('e', stat::FdInfoView::cb_clients);
+            siv.add_global_callback('C', stat::FdInfoView::cb_group_by_cgroup);
+            siv.add_global_callback('U', stat::FdInfoView::cb_group_by_user);
+            siv.add_global_callback('/', stat::FdInfoView::cb_filter);
+            siv.add_global_callback(Event::CtrlChar('n'), stat::FdInfoView::cb_select_next);
+            siv.add_global_callback(Event::CtrlChar('p'), stat::FdInfoView::cb_select_prev);
+        }
+        {
+            layout.add_child(fdinfo.ledger_text.panel("Process GPU Time"));
+            siv.add_global_callback('x', stat::FdInfoView::cb_ledger);
+        }
+        {
+            layout.add_child(event_log.text.panel("Events"));
+            siv.add_global_callback('l', stat::EventLog::cb);
         }
         {
             layout.add_child(sensor.text.panel("Sensors"));
@@ -212,6 +361,7 @@ fn main() {
             layout.add_child(metrics.text.panel(&title));
             siv.add_global_callback('m', stat::GpuMetricsView::cb);
         }
+        layout.add_child(TextView::new_with_content(sampling_info.clone()));
         layout.add_child(TextView::new(TOGGLE_HELP));
 
         siv.add_layer(
@@ -226,23 +376,33 @@ fn main() {
     siv.set_user_data(toggle_opt.clone());
     siv.add_global_callback('q', cursive::Cursive::quit);
     siv.add_global_callback('h', Sampling::cb);
+    siv.add_global_callback('+', Sampling::cb_more_samples);
+    siv.add_global_callback('-', Sampling::cb_less_samples);
+    siv.add_global_callback(']', Sampling::cb_longer_period);
+    siv.add_global_callback('[', Sampling::cb_shorter_period);
 
-    let share_proc_index = Arc::new(Mutex::new(proc_index));
+    let share_proc_index = Arc::new(Mutex::new(proc_index.procs.clone()));
     let cb_sink = siv.cb_sink().clone();
+    siv.add_global_callback('d', stat::ProcDetail::cb(share_proc_index.clone(), proc_root.clone()));
+    // PIDs of another --proc-root (e.g. a fixture tree or the procfs of another namespace)
+    // are not the ones of kill(2)
+    if proc_root == Path::new(stat::PROC_ROOT) {
+        siv.add_global_callback('k', stat::Signal::cb(share_proc_index.clone(), device_nodes.clone()));
+    }
 
     {
         let index = share_proc_index.clone();
-        let mut buf_index: Vec<stat::ProcInfo> = Vec::new();
+        let scan_interval = main_opt.scan_interval();
 
         std::thread::spawn(move || {
             loop {
-                std::thread::sleep(Duration::from_secs(5));
+                std::thread::sleep(scan_interval);
 
-                stat::update_index(&mut buf_index, &device_path, self_pid);
+                proc_index.update();
 
                 let lock = index.lock();
                 if let Ok(mut index) = lock {
-                    *index = buf_index.clone();
+                    *index = proc_index.procs.clone();
                 }
             }
         });
@@ -260,9 +420,21 @@ fn main() {
                 if flags.grbm2 {
                     grbm2.read_reg(&amdgpu_dev);
                 }
+                if flags.grbm_se {
+                    grbm_se.read_reg(&amdgpu_dev);
+                }
+                if flags.srbm {
+                    srbm.read_reg(&amdgpu_dev);
+                }
+                if flags.srbm2 {
+                    srbm2.read_reg(&amdgpu_dev);
+                }
                 if flags.cp_stat {
                     cp_stat.read_reg(&amdgpu_dev);
                 }
+                if flags.watch_reg {
+                    watch_reg.read_reg(&amdgpu_dev);
+                }
 
                 std::thread::sleep(sample.delay);
             }
@@ -274,34 +446,62 @@ fn main() {
                 }
             }
 
-            sample = if flags.high_freq {
-                Sampling::high()
-            } else {
-                Sampling::low()
-            };
+            sample = Sampling::from_opt(&flags);
+            sampling_info.set_content(sample.to_string());
 
             if flags.vram {
                 vram_usage.update_usage(&amdgpu_dev);
             }
 
+            if let Some(activity) = &mut activity {
+                if flags.activity {
+                    activity.update(&amdgpu_dev);
+                }
+            }
+
             if flags.sensor {
                 sensor.print(&amdgpu_dev).unwrap();
             } else {
                 sensor.text.clear();
             }
 
-            if flags.fdinfo {
+            // the Engines panel is summed from fdinfo, events are from diffs of the index
+            if flags.fdinfo || flags.engines || flags.event_log || event_log.has_file() {
                 let lock = index.try_lock();
                 if let Ok(vec_info) = lock {
-                    fdinfo.print(&vec_info, &flags.fdinfo_sort, flags.reverse_sort).unwrap();
-                    fdinfo.interval = sample.to_duration();
-                } else {
-                    fdinfo.interval += sample.to_duration();
+                    fdinfo.filter = flags.fdinfo_filter.clone();
+                    fdinfo.selected = flags.fdinfo_selected;
+                    fdinfo.print(
+                        &vec_info,
+                        &flags.fdinfo_sort,
+                        flags.reverse_sort,
+                        flags.fdinfo_clients,
+                        &flags.fdinfo_group,
+                    ).unwrap();
+                    engines.usage = fdinfo.engines();
+                    event_log.update(&vec_info, &fdinfo.exited);
                 }
-            } else {
+
+                if let Ok(mut opt) = toggle_opt.try_lock() {
+                    opt.fdinfo_rows = fdinfo.rows.clone();
+                }
+            }
+            if !flags.fdinfo {
                 fdinfo.text.clear();
             }
 
+            if flags.fdinfo_ledger {
+                fdinfo.print_ledger().unwrap();
+            } else {
+                fdinfo.ledger_text.clear();
+            }
+
+            if flags.event_log {
+                event_log.print().unwrap();
+            } else {
+                event_log.text.clear();
+            }
+
             if flags.gpu_metrics {
                 if metrics.update_metrics(&amdgpu_dev).is_ok() {
                     metrics.print().unwrap();
@@ -312,10 +512,20 @@ fn main() {
 
             grbm.dump();
             grbm2.dump();
+            grbm_se.dump();
+            srbm.dump();
+            srbm2.dump();
             cp_stat.dump();
+            watch_reg.dump();
 
             vram_usage.set_value();
+            engines.dump();
+            if let Some(activity) = &mut activity {
+                activity.dump();
+            }
             fdinfo.text.set();
+            fdinfo.ledger_text.set();
+            event_log.text.set();
             sensor.text.set();
             metrics.text.set();
 
@@ -326,34 +536,70 @@ fn main() {
     siv.run();
 }
 
+/// `count` register reads spread over the refresh period
 struct Sampling {
-    count: usize,
+    count: u32,
     delay: Duration,
 }
 
 impl Sampling {
-    const fn low() -> Self {
+    /// range of the refresh period changed at runtime, ms
+    const PERIOD_RANGE: std::ops::RangeInclusive<u64> = 100..=10_000;
+
+    fn from_opt(opt: &ToggleOptions) -> Self {
+        // high frequency mode reads the registers in 1/10 of the refresh period
+        let period = Duration::from_millis(opt.refresh_period) / if opt.high_freq { 10 } else { 1 };
+
         Self {
-            count: 100,
-            delay: Duration::from_millis(10),
+            count: opt.sample_count,
+            delay: period / opt.sample_count,
         }
     }
 
-    const fn high() -> Self {
-        Self {
-            count: 100,
-            delay: Duration::from_millis(1),
+    pub fn cb(siv: &mut cursive::Cursive) {
+        {
+            let mut opt = siv.user_data::<Opt>().unwrap().lock().unwrap();
+            opt.high_freq ^= true;
         }
     }
 
-    fn to_duration(&self) -> Duration {
-        self.delay * self.count as u32
+    pub fn cb_more_samples(siv: &mut cursive::Cursive) {
+        {
+            let mut opt = siv.user_data::<Opt>().unwrap().lock().unwrap();
+            opt.sample_count = (opt.sample_count * 2).min(args::MAX_SAMPLES);
+        }
     }
 
-    pub fn cb(siv: &mut cursive::Cursive) {
+    pub fn cb_less_samples(siv: &mut cursive::Cursive) {
         {
             let mut opt = siv.user_data::<Opt>().unwrap().lock().unwrap();
-            opt.high_freq ^= true;
+            opt.sample_count = (opt.sample_count / 2).max(1);
+        }
+    }
+
+    pub fn cb_longer_period(siv: &mut cursive::Cursive) {
+        {
+            let mut opt = siv.user_data::<Opt>().unwrap().lock().unwrap();
+            opt.refresh_period = (opt.refresh_period * 2).min(*Self::PERIOD_RANGE.end());
         }
     }
+
+    pub fn cb_shorter_period(siv: &mut cursive::Cursive) {
+        {
+            let mut opt = siv.user_data::<Opt>().unwrap().lock().unwrap();
+            opt.refresh_period = (opt.refresh_period / 2).max(*Self::PERIOD_RANGE.start());
+        }
+    }
+}
+
+impl std::fmt::Display for Sampling {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            " Sampling: {count} reads every {delay:?} ({period} ms)",
+            count = self.count,
+            delay = self.delay,
+            period = (self.delay * self.count).as_millis(),
+        )
+    }
 }
@@ -0,0 +1,154 @@
+use libdrm_amdgpu_sys::AMDGPU::{DeviceHandle, GPU_INFO};
+use crate::stat::{PCType, WatchReg};
+use crate::args::MainOpt;
+use std::time::Duration;
+use serde_json::{json, Map, Value};
+
+/// default interval between snapshots
+const INTERVAL_MS: u64 = 100;
+
+/// A status register read once, with the fields of the bit tables decoded
+struct RegSnapshot {
+    name: String,
+    offset: u32,
+    raw: Result<u32, i32>,
+    fields: Vec<(String, u32)>,
+}
+
+impl RegSnapshot {
+    fn read(
+        amdgpu_dev: &DeviceHandle,
+        name: &str,
+        offset: u32,
+        index: &[(String, usize)],
+    ) -> Self {
+        let raw = amdgpu_dev.read_mm_registers(offset);
+        let fields = match raw {
+            Ok(raw) => index.iter().map(|(name, pos)| (name.clone(), (raw >> pos) & 0b1)).collect(),
+            Err(_) => Vec::new(),
+        };
+
+        Self { name: name.to_string(), offset, raw, fields }
+    }
+
+    fn from_watch_reg(amdgpu_dev: &DeviceHandle, reg: &WatchReg) -> Self {
+        let raw = amdgpu_dev.read_mm_registers(reg.offset);
+        let fields = match raw {
+            Ok(raw) => vec![(reg.name.clone(), reg.field(raw) as u32)],
+            Err(_) => Vec::new(),
+        };
+
+        Self { name: reg.to_string(), offset: reg.offset, raw, fields }
+    }
+
+    fn print(&self) {
+        let raw = match self.raw {
+            Ok(raw) => raw,
+            Err(err) => {
+                println!("{} ({:#06X}): not allowed ({err})", self.name, self.offset);
+                return;
+            },
+        };
+
+        println!("{} ({:#06X}): {raw:#010X}", self.name, self.offset);
+
+        for (name, val) in &self.fields {
+            println!("    {name:<40}: {val}");
+        }
+    }
+
+    /// `{ "offset", "error" }` for registers not allowed to be read
+    fn json_value(&self) -> Value {
+        let raw = match self.raw {
+            Ok(raw) => raw,
+            Err(err) => return json!({
+                "offset": format!("{:#06X}", self.offset),
+                "error": err,
+            }),
+        };
+        let fields: Map<String, Value> = self.fields.iter()
+            .map(|(name, val)| (name.clone(), (*val).into()))
+            .collect();
+
+        json!({
+            "offset": format!("{:#06X}", self.offset),
+            "raw": format!("{raw:#010X}"),
+            "fields": fields,
+        })
+    }
+}
+
+/// Reads the status registers `count` times and prints every decoded field with the raw value
+pub fn print(amdgpu_dev: &DeviceHandle, main_opt: &MainOpt, count: u32) {
+    let json_output = main_opt.json_output;
+    let ext_info = amdgpu_dev.device_info().unwrap();
+    let chip_class = ext_info.get_chip_class().to_string();
+    let asic_name = ext_info.get_asic_name().to_string();
+    let bit_tables = main_opt.bit_tables();
+    let watch = main_opt.watch_counter(&bit_tables);
+
+    let index: Vec<(PCType, Vec<(String, usize)>)> = PCType::ALL.into_iter().map(|pc_type| {
+        let mut index = bit_tables.index(&pc_type, &chip_class, &asic_name);
+        index.sort_by_key(|(_, pos)| std::cmp::Reverse(*pos));
+
+        (pc_type, index)
+    }).collect();
+    let interval = match main_opt.refresh_period {
+        0 => Duration::from_millis(INTERVAL_MS),
+        ms => Duration::from_millis(ms),
+    };
+    let mut vec_value: Vec<Value> = Vec::new();
+
+    for i in 0..count {
+        if i != 0 {
+            std::thread::sleep(interval);
+        }
+
+        let snapshot: Vec<RegSnapshot> = index.iter()
+            .map(|(pc_type, index)| RegSnapshot::read(amdgpu_dev, pc_type.reg_name(), pc_type.offset(), index))
+            .chain(watch.regs.iter().map(|reg| RegSnapshot::from_watch_reg(amdgpu_dev, reg)))
+            .collect();
+
+        if json_output {
+            let m: Map<String, Value> = snapshot.iter()
+                .map(|reg| (reg.name.clone(), reg.json_value()))
+                .collect();
+
+            vec_value.push(m.into());
+        } else {
+            println!("--- #{i} ---");
+            for reg in &snapshot {
+                reg.print();
+            }
+            println!();
+        }
+    }
+
+    if json_output {
+        println!("{}", vec_value.into_iter().collect::<Value>());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn snapshot_json() {
+        let reg = RegSnapshot {
+            name: "mmGRBM_STATUS".to_string(),
+            offset: 0x2004,
+            raw: Ok(0x8000_0000),
+            fields: vec![("Graphics Pipe".to_string(), 1)],
+        };
+        let v = reg.json_value();
+
+        assert_eq!(v["offset"], "0x2004");
+        assert_eq!(v["raw"], "0x80000000");
+        assert_eq!(v["fields"]["Graphics Pipe"], 1);
+
+        let reg = RegSnapshot { raw: Err(-22), fields: Vec::new(), ..reg };
+
+        assert_eq!(reg.json_value(), json!({ "offset": "0x2004", "error": -22 }));
+    }
+}
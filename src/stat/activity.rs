@@ -0,0 +1,134 @@
+use super::{DeviceHandle, Opt, TopView, UsageBars, toggle_view};
+use libdrm_amdgpu_sys::AMDGPU::{GpuMetrics, MetricsInfo, SENSOR_INFO::SENSOR_TYPE};
+use serde_json::{json, Map, Value};
+use std::fs;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Source {
+    /// AMDGPU_INFO_SENSOR_GPU_LOAD
+    GpuLoad,
+    /// sysfs "gpu_busy_percent"
+    GpuBusy,
+    MetricsGfx,
+    MetricsUmc,
+    MetricsMm,
+}
+
+impl Source {
+    const ALL: [Self; 5] = [Self::GpuLoad, Self::GpuBusy, Self::MetricsGfx, Self::MetricsUmc, Self::MetricsMm];
+
+    const fn label(&self) -> &'static str {
+        match self {
+            Self::GpuLoad => "GFX (GPU_LOAD sensor)",
+            Self::GpuBusy => "GFX (gpu_busy_percent)",
+            Self::MetricsGfx => "GFX (gpu_metrics)",
+            Self::MetricsUmc => "Memory (gpu_metrics)",
+            Self::MetricsMm => "Media (gpu_metrics)",
+        }
+    }
+}
+
+/// Utilization without MMIO register reads, from the sensor, sysfs and gpu_metrics.
+/// Sources not available on the device at startup are not shown.
+pub struct ActivityView {
+    sysfs_path: PathBuf,
+    values: Vec<(Source, Option<u32>)>,
+    bars: UsageBars,
+}
+
+impl ActivityView {
+    pub const TITLE: &str = "Activity (passive, no register reads)";
+
+    pub fn new(amdgpu_dev: &DeviceHandle, stats_window: Duration) -> Self {
+        let sysfs_path = amdgpu_dev.get_sysfs_path().unwrap_or_default();
+        let mut s = Self {
+            sysfs_path,
+            values: Source::ALL.iter().map(|src| (*src, None)).collect(),
+            bars: UsageBars::new(Vec::new(), stats_window),
+        };
+
+        s.update(amdgpu_dev);
+        s.values.retain(|(_, val)| val.is_some());
+        s.bars = UsageBars::new(s.values.iter().map(|(src, _)| src.label().to_string()).collect(), stats_window);
+
+        s
+    }
+
+    pub fn update(&mut self, amdgpu_dev: &DeviceHandle) {
+        let metrics = amdgpu_dev.get_gpu_metrics_from_sysfs_path(&self.sysfs_path).ok();
+        let metrics_value = |get: fn(&GpuMetrics) -> Option<u16>| -> Option<u32> {
+            // u16::MAX: not supported by the firmware
+            metrics.as_ref().and_then(get).filter(|v| *v != u16::MAX).map(|v| v as u32)
+        };
+        let gpu_busy = fs::read_to_string(self.sysfs_path.join("gpu_busy_percent")).ok()
+            .and_then(|s| s.trim().parse::<u32>().ok());
+
+        for (src, val) in self.values.iter_mut() {
+            *val = match src {
+                Source::GpuLoad => amdgpu_dev.sensor_info(SENSOR_TYPE::GPU_LOAD).ok(),
+                Source::GpuBusy => gpu_busy,
+                Source::MetricsGfx => metrics_value(|m| m.get_average_gfx_activity()),
+                Source::MetricsUmc => metrics_value(|m| m.get_average_umc_activity()),
+                Source::MetricsMm => metrics_value(|m| m.get_average_mm_activity()),
+            };
+        }
+    }
+
+    pub fn top_view(
+        &self,
+        visible: bool,
+    ) -> TopView {
+        self.bars.top_view(Self::TITLE, visible)
+    }
+
+    fn usage(&self) -> Vec<u32> {
+        self.values.iter().map(|(_, val)| val.unwrap_or(0).min(100)).collect()
+    }
+
+    pub fn set_value(&self) {
+        self.bars.set_value(&self.usage());
+    }
+
+    /// Adds the values of the current refresh period to the stats window, failed reads are skipped
+    pub fn update_stats(&mut self) {
+        let now = Instant::now();
+
+        for (stats, (_, val)) in self.bars.stats.iter_mut().zip(self.values.iter()) {
+            let Some(val) = val else { continue };
+            stats.push((*val).min(100), now);
+        }
+    }
+
+    pub fn dump(&mut self) {
+        self.update_stats();
+        self.set_value();
+    }
+
+    pub fn json_value(&self) -> Value {
+        let mut m = Map::new();
+
+        for (row, (src, val)) in self.values.iter().enumerate() {
+            m.insert(
+                src.label().to_string(),
+                json!({
+                    "value": val,
+                    "unit": "%",
+                    "stats": self.bars.stats_json_value(row),
+                }),
+            );
+        }
+
+        m.into()
+    }
+
+    pub fn cb(siv: &mut cursive::Cursive) {
+        {
+            let mut opt = siv.user_data::<Opt>().unwrap().lock().unwrap();
+            opt.activity ^= true;
+        }
+
+        siv.call_on_name(Self::TITLE, toggle_view);
+    }
+}
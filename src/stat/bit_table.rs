@@ -0,0 +1,339 @@
+use super::{PCType, WatchReg};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::Path;
+use serde_json::Value;
+
+const EMBEDDED: &str = include_str!("bit_tables.json");
+const REG_NAMES: [&str; 6] = ["GRBM", "GRBM2", "SRBM", "SRBM2", "CP_STAT", "GRBM_STATUS_SE"];
+/// key of the register watch list, e.g. `{ "watch": ["GRBM_GUI_ACTIVE:0x2004:31"] }`
+const WATCH_KEY: &str = "watch";
+
+/// Bit layout of a register for some chip classes and/or ASICs
+#[derive(Clone, Debug)]
+struct BitEntry {
+    chip_class: Vec<String>,
+    asic: Vec<String>,
+    bits: Vec<(String, usize)>,
+}
+
+type Layer = HashMap<String, Vec<BitEntry>>;
+
+/// Bit tables of performance counter registers, keyed by register name
+/// e.g. `{ "GRBM": [{ "chip_class": ["GFX10"], "asic": ["NAVI10"], "bits": [["Graphics Pipe", 31]] }] }`
+#[derive(Clone, Debug, Default)]
+pub struct BitTables {
+    /// the user override file first, then the embedded tables
+    layers: Vec<Layer>,
+    /// user-defined registers, same format as `--watch-reg`
+    pub watch: Vec<WatchReg>,
+}
+
+impl BitTables {
+    pub fn embedded() -> Self {
+        let (layer, watch) = parse(EMBEDDED).expect("invalid embedded bit tables");
+
+        Self {
+            layers: vec![layer],
+            watch,
+        }
+    }
+
+    /// The embedded tables, entries of `path` take precedence over them
+    pub fn with_override(path: Option<&Path>) -> Result<Self, String> {
+        let mut tables = Self::embedded();
+
+        if let Some(path) = path {
+            let s = fs::read_to_string(path).map_err(|err| format!("{}: {err}", path.display()))?;
+            let (layer, watch) = parse(&s).map_err(|err| format!("{}: {err}", path.display()))?;
+
+            tables.layers.insert(0, layer);
+            tables.watch.extend(watch);
+        }
+
+        Ok(tables)
+    }
+
+    /// Bits of `pc_type` for the device, an ASIC match is preferred over a chip class match.
+    /// A chip class not in the tables (e.g. a newer one) uses the nearest lower chip class.
+    /// Returns an empty Vec if the register is not defined for the device.
+    pub fn index(&self, pc_type: &PCType, chip_class: &str, asic: &str) -> Vec<(String, usize)> {
+        self.index_by_name(&pc_type.to_string(), chip_class, asic)
+    }
+
+    /// `index` for a register without a `PCType`, e.g. "GRBM_STATUS_SE"
+    pub fn index_by_name(&self, reg_name: &str, chip_class: &str, asic: &str) -> Vec<(String, usize)> {
+        let chip_class = self.nearest_chip_class(&normalize(chip_class));
+        let asic = normalize(asic);
+
+        for layer in &self.layers {
+            let Some(entries) = layer.get(reg_name) else { continue };
+
+            let entry = entries.iter().find(|e| e.asic.contains(&asic))
+                .or_else(|| entries.iter().find(|e| e.chip_class.contains(&chip_class)));
+
+            if let Some(entry) = entry {
+                return entry.bits.clone();
+            }
+        }
+
+        Vec::new()
+    }
+
+    fn nearest_chip_class(&self, chip_class: &str) -> String {
+        let known: HashSet<&String> = self.layers.iter()
+            .flat_map(|layer| layer.values().flatten())
+            .flat_map(|entry| entry.chip_class.iter())
+            .collect();
+
+        if known.contains(&chip_class.to_string()) { return chip_class.to_string() }
+        let Some(ver) = chip_class_version(chip_class) else { return chip_class.to_string() };
+
+        known.into_iter()
+            .filter_map(|name| Some((chip_class_version(name)?, name)))
+            .filter(|(known_ver, _)| *known_ver <= ver)
+            .max()
+            .map(|(_, name)| name.to_string())
+            .unwrap_or_else(|| chip_class.to_string())
+    }
+}
+
+/// "GFX10_3" -> (10, 3), "GFX9" -> (9, 0)
+fn chip_class_version(s: &str) -> Option<(u32, u32)> {
+    let s = s.strip_prefix("GFX")?;
+    let (major, minor) = s.split_once('_').unwrap_or((s, "0"));
+
+    Some((major.parse().ok()?, minor.parse().ok()?))
+}
+
+/// "CHIP_NAVI10" -> "NAVI10", "gfx10_3" -> "GFX10_3"
+fn normalize(s: &str) -> String {
+    let s = s.to_uppercase();
+
+    s.strip_prefix("CHIP_").map(|s| s.to_string()).unwrap_or(s)
+}
+
+fn parse(s: &str) -> Result<(Layer, Vec<WatchReg>), String> {
+    let value: Value = serde_json::from_str(s).map_err(|err| err.to_string())?;
+    let obj = value.as_object().ok_or("top level is not an object")?;
+    let mut layer = Layer::new();
+    let mut watch = Vec::new();
+
+    for (reg_name, entries) in obj {
+        if reg_name == WATCH_KEY {
+            watch = parse_watch(entries).map_err(|err| format!("{WATCH_KEY}: {err}"))?;
+            continue;
+        }
+
+        if !REG_NAMES.contains(&reg_name.as_str()) {
+            return Err(format!("unknown register: {reg_name} (expected one of {REG_NAMES:?} or {WATCH_KEY:?})"));
+        }
+
+        let entries = entries.as_array().ok_or(format!("{reg_name}: not an array"))?;
+        let entries = entries.iter().enumerate()
+            .map(|(i, entry)| parse_entry(entry).map_err(|err| format!("{reg_name}[{i}]: {err}")))
+            .collect::<Result<Vec<BitEntry>, String>>()?;
+
+        layer.insert(reg_name.to_string(), entries);
+    }
+
+    Ok((layer, watch))
+}
+
+fn parse_watch(entries: &Value) -> Result<Vec<WatchReg>, String> {
+    let entries = entries.as_array().ok_or("not an array")?;
+
+    entries.iter().enumerate().map(|(i, entry)| {
+        let s = entry.as_str().ok_or(format!("[{i}]: not a string: {entry}"))?;
+
+        s.parse::<WatchReg>().map_err(|err| format!("[{i}]: {err}"))
+    }).collect()
+}
+
+fn parse_entry(entry: &Value) -> Result<BitEntry, String> {
+    let str_array = |key: &str| -> Result<Vec<String>, String> {
+        let Some(v) = entry.get(key) else { return Ok(Vec::new()) };
+        let arr = v.as_array().ok_or(format!("{key}: not an array"))?;
+
+        arr.iter()
+            .map(|s| s.as_str().map(normalize).ok_or(format!("{key}: not a string: {s}")))
+            .collect()
+    };
+
+    let chip_class = str_array("chip_class")?;
+    let asic = str_array("asic")?;
+
+    if chip_class.is_empty() && asic.is_empty() {
+        return Err("either \"chip_class\" or \"asic\" is required".to_string());
+    }
+
+    let bits = entry.get("bits").and_then(|v| v.as_array()).ok_or("\"bits\" is required")?;
+    let mut positions = HashSet::new();
+    let bits = bits.iter().enumerate().map(|(i, bit)| {
+        let (name, pos) = match bit.as_array().map(|a| a.as_slice()) {
+            Some([name, pos]) => (name.as_str(), pos.as_u64()),
+            _ => (None, None),
+        };
+        let (Some(name), Some(pos)) = (name, pos) else {
+            return Err(format!("bits[{i}]: expected [\"name\", <bit>], found {bit}"));
+        };
+
+        if name.is_empty() {
+            return Err(format!("bits[{i}]: empty name"));
+        }
+        if 32 <= pos {
+            return Err(format!("bits[{i}]: bit {pos} is out of range (0-31)"));
+        }
+        if !positions.insert(pos) {
+            return Err(format!("bits[{i}]: duplicate bit {pos}"));
+        }
+
+        Ok((name.to_string(), pos as usize))
+    }).collect::<Result<Vec<(String, usize)>, String>>()?;
+
+    Ok(BitEntry { chip_class, asic, bits })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(bits: &[(String, usize)]) -> Vec<&str> {
+        bits.iter().map(|(name, _)| name.as_str()).collect()
+    }
+
+    fn tables(s: &str) -> BitTables {
+        let (layer, watch) = parse(s).unwrap();
+
+        BitTables { layers: vec![layer], watch }
+    }
+
+    #[test]
+    fn embedded_tables() {
+        let tables = BitTables::embedded();
+        let grbm = tables.index(&PCType::GRBM, "GFX10_3", "CHIP_NAVI21");
+
+        assert!(names(&grbm).contains(&"Geometry Engine"));
+        assert!(names(&tables.index(&PCType::GRBM, "GFX9", "CHIP_VEGA10")).contains(&"Work Distributor"));
+        assert!(tables.index(&PCType::SRBM, "GFX10_3", "CHIP_NAVI21").is_empty());
+        assert!(tables.watch.is_empty());
+    }
+
+    #[test]
+    fn asic_entries() {
+        let tables = BitTables::embedded();
+
+        assert_eq!(names(&tables.index(&PCType::SRBM, "GFX8", "CHIP_POLARIS10")), ["UVD"]);
+        assert!(tables.index(&PCType::SRBM, "GFX8", "CHIP_TOPAZ").is_empty());
+        assert!(tables.index(&PCType::SRBM, "GFX6", "CHIP_HAINAN").is_empty());
+        assert_eq!(names(&tables.index(&PCType::SRBM2, "GFX8", "CHIP_STONEY")), ["VCE0", "SDMA0"]);
+        assert_eq!(names(&tables.index(&PCType::SRBM2, "GFX8", "CHIP_TOPAZ")), ["SDMA0", "SDMA1"]);
+    }
+
+    #[test]
+    fn nearest_lower_chip_class() {
+        let tables = BitTables::embedded();
+        let gfx11 = tables.index(&PCType::GRBM, "GFX11", "CHIP_UNKNOWN");
+
+        assert!(!gfx11.is_empty());
+        // no layout is defined for GFX11, it uses the one of GFX10_3
+        assert_eq!(gfx11, tables.index(&PCType::GRBM, "GFX10_3", "CHIP_UNKNOWN"));
+        assert_eq!(
+            tables.index(&PCType::CP_STAT, "GFX11", "CHIP_UNKNOWN"),
+            tables.index(&PCType::CP_STAT, "GFX10_3", "CHIP_UNKNOWN"),
+        );
+        assert_eq!(tables.index(&PCType::GRBM, "GFX12", "CHIP_UNKNOWN"), gfx11);
+        assert_eq!(tables.index(&PCType::GRBM, "GFX11_5", "CHIP_UNKNOWN"), gfx11);
+        // known chip classes without the register do not fall back
+        assert!(tables.index(&PCType::SRBM, "GFX9", "CHIP_UNKNOWN").is_empty());
+        assert!(tables.index(&PCType::SRBM, "GFX12", "CHIP_UNKNOWN").is_empty());
+        // older than all chip classes in the tables
+        assert!(tables.index(&PCType::GRBM, "GFX5", "CHIP_UNKNOWN").is_empty());
+        assert!(tables.index(&PCType::GRBM, "CLASS_UNKNOWN", "CHIP_UNKNOWN").is_empty());
+    }
+
+    #[test]
+    fn grbm_status_se() {
+        let tables = BitTables::embedded();
+        let gfx9 = tables.index_by_name("GRBM_STATUS_SE", "GFX9", "CHIP_VEGA10");
+        let gfx10 = tables.index_by_name("GRBM_STATUS_SE", "GFX10_3", "CHIP_NAVI21");
+
+        assert!(names(&gfx9).contains(&"Vertex Grouper / Tessellator"));
+        assert!(names(&gfx10).contains(&"Texture Cache per Pipe"));
+        assert!(!names(&gfx10).contains(&"Vertex Grouper / Tessellator"));
+        assert_eq!(
+            tables.index_by_name("GRBM_STATUS_SE", "GFX12", "CHIP_UNKNOWN"),
+            tables.index_by_name("GRBM_STATUS_SE", "GFX11", "CHIP_UNKNOWN"),
+        );
+        assert_eq!(
+            tables.index_by_name("GRBM", "GFX9", "CHIP_VEGA10"),
+            tables.index(&PCType::GRBM, "GFX9", "CHIP_VEGA10"),
+        );
+    }
+
+    #[test]
+    fn chip_class_versions() {
+        assert_eq!(chip_class_version("GFX9"), Some((9, 0)));
+        assert_eq!(chip_class_version("GFX10_3"), Some((10, 3)));
+        assert_eq!(chip_class_version("R600"), None);
+        assert_eq!(chip_class_version("GFX"), None);
+        assert!(chip_class_version("GFX10_3").unwrap() < chip_class_version("GFX11").unwrap());
+    }
+
+    #[test]
+    fn override_layer() {
+        let mut tables = BitTables::embedded();
+        let (layer, watch) = parse(r#"{
+            "GRBM": [{ "asic": ["CHIP_NAVI10"], "bits": [["GUI Active", 31]] }],
+            "watch": ["GUI_ACTIVE:0x2004:31"]
+        }"#).unwrap();
+        tables.layers.insert(0, layer);
+        tables.watch.extend(watch);
+
+        assert_eq!(names(&tables.index(&PCType::GRBM, "GFX10", "CHIP_NAVI10")), ["GUI Active"]);
+        // falls through to the embedded tables
+        assert!(names(&tables.index(&PCType::GRBM, "GFX10", "CHIP_NAVI14")).contains(&"Geometry Engine"));
+        assert_eq!(tables.watch.len(), 1);
+    }
+
+    #[test]
+    fn asic_is_preferred() {
+        let tables = tables(r#"{
+            "CP_STAT": [
+                { "chip_class": ["gfx9"], "bits": [["A", 0]] },
+                { "asic": ["vega20"], "bits": [["B", 1]] }
+            ]
+        }"#);
+
+        assert_eq!(names(&tables.index(&PCType::CP_STAT, "GFX9", "CHIP_VEGA20")), ["B"]);
+        assert_eq!(names(&tables.index(&PCType::CP_STAT, "GFX9", "CHIP_VEGA10")), ["A"]);
+    }
+
+    #[test]
+    fn invalid_tables() {
+        for (s, err) in [
+            ("", "EOF"),
+            ("[]", "top level is not an object"),
+            (r#"{ "GRBM3": [] }"#, "unknown register: GRBM3"),
+            (r#"{ "GRBM": {} }"#, "GRBM: not an array"),
+            (r#"{ "GRBM": [{ "bits": [] }] }"#, "GRBM[0]: either \"chip_class\" or \"asic\" is required"),
+            (r#"{ "GRBM": [{ "asic": "NAVI10", "bits": [] }] }"#, "GRBM[0]: asic: not an array"),
+            (r#"{ "GRBM": [{ "asic": [10], "bits": [] }] }"#, "GRBM[0]: asic: not a string: 10"),
+            (r#"{ "GRBM": [{ "chip_class": ["GFX9"] }] }"#, "GRBM[0]: \"bits\" is required"),
+            (r#"{ "GRBM": [{ "chip_class": ["GFX9"], "bits": [["A"]] }] }"#, "GRBM[0]: bits[0]: expected"),
+            (r#"{ "GRBM": [{ "chip_class": ["GFX9"], "bits": [["A", -1]] }] }"#, "GRBM[0]: bits[0]: expected"),
+            (r#"{ "GRBM": [{ "chip_class": ["GFX9"], "bits": [["", 1]] }] }"#, "GRBM[0]: bits[0]: empty name"),
+            (r#"{ "GRBM": [{ "chip_class": ["GFX9"], "bits": [["A", 32]] }] }"#, "GRBM[0]: bits[0]: bit 32 is out of range"),
+            (
+                r#"{ "GRBM": [{ "chip_class": ["GFX9"], "bits": [["A", 1], ["B", 1]] }] }"#,
+                "GRBM[0]: bits[1]: duplicate bit 1",
+            ),
+            (r#"{ "watch": "A:0x2004:31" }"#, "watch: not an array"),
+            (r#"{ "watch": [1] }"#, "watch: [0]: not a string: 1"),
+            (r#"{ "watch": ["A"] }"#, "watch: [0]: "),
+        ] {
+            let Err(e) = parse(s) else { panic!("{s} is accepted") };
+            assert!(e.contains(err), "{s}: {e}");
+        }
+    }
+}
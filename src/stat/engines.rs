@@ -0,0 +1,95 @@
+use super::{Opt, TopView, UsageBars, toggle_view, EngineUsage};
+use serde_json::{Map, Value};
+use std::time::Duration;
+
+/// Engine utilization summed from fdinfo of all processes, not from registers
+pub struct EnginesView {
+    pub usage: EngineUsage,
+    bars: UsageBars,
+}
+
+impl EnginesView {
+    const TITLE: &str = "Engines (fdinfo)";
+    const LABELS: [&str; 6] = ["GFX", "Compute", "DMA", "VCN Decode", "VCN Encode", "JPEG"];
+
+    pub fn new(stats_window: Duration) -> Self {
+        Self {
+            usage: EngineUsage::default(),
+            bars: UsageBars::new(Self::LABELS.iter().map(|name| name.to_string()).collect(), stats_window),
+        }
+    }
+
+    fn values(&self) -> [f64; 6] {
+        let u = &self.usage;
+
+        [u.gfx, u.compute, u.dma, u.dec, u.enc_total(), u.vcn_jpeg]
+    }
+
+    fn rounded(&self) -> Vec<u32> {
+        self.values().iter().map(|val| val.round() as u32).collect()
+    }
+
+    pub fn top_view(
+        &self,
+        visible: bool,
+    ) -> TopView {
+        self.bars.top_view(Self::TITLE, visible)
+    }
+
+    pub fn set_value(&self) {
+        self.bars.set_value(&self.rounded());
+    }
+
+    /// Adds the usage of the current refresh period to the stats window
+    pub fn update_stats(&mut self) {
+        let usage = self.rounded();
+        self.bars.update_stats(&usage);
+    }
+
+    pub fn dump(&mut self) {
+        self.update_stats();
+        self.set_value();
+    }
+
+    pub fn json_value(&self) -> Value {
+        let mut m = Map::new();
+
+        for (row, (name, val)) in Self::LABELS.iter().zip(self.values()).enumerate() {
+            m.insert(name.to_string(), self.bars.json_value(row, val));
+        }
+
+        m.into()
+    }
+
+    pub fn cb(siv: &mut cursive::Cursive) {
+        {
+            let mut opt = siv.user_data::<Opt>().unwrap().lock().unwrap();
+            opt.engines ^= true;
+        }
+
+        siv.call_on_name(Self::TITLE, toggle_view);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn json_value_with_stats() {
+        let mut engines = EnginesView::new(Duration::from_secs(60));
+
+        for gfx in [20.4, 60.6] {
+            engines.usage = EngineUsage { gfx, enc: 80.0, uvd_enc: 40.0, ..Default::default() };
+            engines.update_stats();
+        }
+
+        let v = engines.json_value();
+        assert_eq!(v["GFX"]["usage"], 60.6);
+        assert_eq!(v["GFX"]["stats"]["min"], 20);
+        assert_eq!(v["GFX"]["stats"]["max"], 61);
+        // "enc" and "enc_1" are clamped to 100%
+        assert_eq!(v["VCN Encode"]["usage"], 100.0);
+        assert!(v["JPEG"]["stats"].is_object());
+    }
+}
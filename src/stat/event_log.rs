@@ -0,0 +1,235 @@
+use super::{Text, Opt, ProcInfo, ProcHistory, fmt_duration};
+use std::collections::{HashMap, VecDeque};
+use std::fmt::{self, Write as _};
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write as _};
+use std::path::Path;
+use std::time::SystemTime;
+use serde_json::{json, Value};
+
+/// number of events shown in the panel
+const ROWS: usize = 10;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EventKind {
+    /// the process was already using the device at startup
+    Running,
+    Start,
+    Stop,
+}
+
+impl fmt::Display for EventKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match self {
+            Self::Running => "running",
+            Self::Start => "start",
+            Self::Stop => "stop",
+        };
+
+        write!(f, "{s}")
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct ProcEvent {
+    pub time: SystemTime,
+    pub kind: EventKind,
+    pub pid: i32,
+    pub name: String,
+    /// usage of the process, only for `EventKind::Stop`
+    pub history: Option<ProcHistory>,
+}
+
+impl ProcEvent {
+    pub fn json_value(&self) -> Value {
+        let mut v = json!({
+            "time": fmt_utc(self.time),
+            "event": self.kind.to_string(),
+            "pid": self.pid,
+            "name": self.name,
+        });
+
+        if let (Some(m), Some(hist)) = (v.as_object_mut(), &self.history) {
+            let hist = hist.json_value();
+            for key in ["lifetime", "peak VRAM Usage", "GPU Time"] {
+                m.insert(key.to_string(), hist[key].clone());
+            }
+        }
+
+        v
+    }
+}
+
+/// Log of processes starting/stopping to use the device, from diffs of the process index
+#[derive(Default)]
+pub struct EventLog {
+    /// PIDs and names of the previous index
+    known: HashMap<i32, String>,
+    initialized: bool,
+    pub events: VecDeque<ProcEvent>,
+    file: Option<File>,
+    /// the file is closed after a failed write, the error is shown in the panel
+    write_error: Option<String>,
+    pub text: Text,
+}
+
+impl EventLog {
+    /// Events are appended to `path` as JSON lines
+    pub fn with_file(path: &Path) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+
+        Ok(Self {
+            file: Some(file),
+            ..Default::default()
+        })
+    }
+
+    pub fn has_file(&self) -> bool {
+        self.file.is_some()
+    }
+
+    /// `exited`: `FdInfoView::exited`, updated by `FdInfoView::update_exited` for `procs`
+    pub fn update(&mut self, procs: &[ProcInfo], exited: &VecDeque<ProcHistory>) {
+        let now = SystemTime::now();
+        let kind = if self.initialized { EventKind::Start } else { EventKind::Running };
+        let mut new_events: Vec<ProcEvent> = Vec::new();
+
+        for p in procs {
+            if self.known.contains_key(&p.pid) { continue }
+
+            new_events.push(ProcEvent {
+                time: now,
+                kind,
+                pid: p.pid,
+                name: p.name.clone(),
+                history: None,
+            });
+        }
+
+        let mut stopped: Vec<(i32, String)> = self.known.iter()
+            .filter(|(pid, _)| !procs.iter().any(|p| p.pid == **pid))
+            .map(|(pid, name)| (*pid, name.clone()))
+            .collect();
+        stopped.sort_by_key(|(pid, _)| *pid);
+
+        for (pid, name) in stopped {
+            new_events.push(ProcEvent {
+                time: now,
+                kind: EventKind::Stop,
+                pid,
+                name,
+                history: exited.iter().rev().find(|hist| hist.pid == pid).cloned(),
+            });
+        }
+
+        self.known = procs.iter().map(|p| (p.pid, p.name.clone())).collect();
+        self.initialized = true;
+
+        for ev in new_events {
+            self.push(ev);
+        }
+    }
+
+    fn push(&mut self, ev: ProcEvent) {
+        if let Some(f) = &mut self.file {
+            if let Err(err) = writeln!(f, "{}", ev.json_value()) {
+                self.write_error = Some(format!("Failed to write the event log: {err}"));
+                self.file = None;
+            }
+        }
+
+        if ROWS <= self.events.len() {
+            self.events.pop_front();
+        }
+        self.events.push_back(ev);
+    }
+
+    /// For the JSON mode, which has no panel to show the error
+    pub fn take_write_error(&mut self) -> Option<String> {
+        self.write_error.take()
+    }
+
+    pub fn print(&mut self) -> Result<(), fmt::Error> {
+        self.text.clear();
+
+        if let Some(err) = &self.write_error {
+            writeln!(self.text.buf, " {err}")?;
+        }
+
+        for ev in self.events.iter().rev() {
+            write!(
+                self.text.buf,
+                " {time} {kind:<7} {name:15} ({pid:>8})",
+                time = fmt_utc(ev.time),
+                kind = ev.kind.to_string(),
+                name = ev.name,
+                pid = ev.pid,
+            )?;
+
+            if let Some(hist) = &ev.history {
+                write!(
+                    self.text.buf,
+                    " | {lifetime} | peak VRAM {vram:>5} MiB",
+                    lifetime = fmt_duration(hist.lifetime()),
+                    vram = hist.peak_vram >> 10,
+                )?;
+            }
+
+            writeln!(self.text.buf)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn cb(siv: &mut cursive::Cursive) {
+        {
+            let mut opt = siv.user_data::<Opt>().unwrap().lock().unwrap();
+            opt.event_log ^= true;
+        }
+    }
+}
+
+/// e.g. "2023-01-02T03:04:05Z"
+pub fn fmt_utc(time: SystemTime) -> String {
+    let secs = time.duration_since(SystemTime::UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+    let (days, rem) = ((secs / 86400) as i64, secs % 86400);
+
+    // ref: Howard Hinnant, "chrono-Compatible Low-Level Date Algorithms", civil_from_days
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let d = doy - (153 * mp + 2) / 5 + 1;
+    let m = if mp < 10 { mp + 3 } else { mp - 9 };
+    let y = yoe + era * 400 + if m <= 2 { 1 } else { 0 };
+
+    format!(
+        "{y:04}-{m:02}-{d:02}T{:02}:{:02}:{:02}Z",
+        rem / 3600,
+        (rem / 60) % 60,
+        rem % 60,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn write_error_is_shown_once() {
+        let Ok(mut log) = EventLog::with_file(Path::new("/dev/full")) else { return };
+        let procs = [ProcInfo { pid: 100, name: "glxgears".to_string(), ..Default::default() }];
+
+        log.update(&procs, &VecDeque::new());
+        log.update(&[], &VecDeque::new());
+        assert!(!log.has_file());
+        assert_eq!(log.events.len(), 2);
+
+        log.print().unwrap();
+        assert_eq!(log.text.buf.matches("Failed to write the event log").count(), 1);
+        assert!(log.take_write_error().is_some());
+        assert!(log.take_write_error().is_none());
+    }
+}
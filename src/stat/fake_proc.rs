@@ -0,0 +1,61 @@
+use std::fs;
+use std::os::unix::fs::symlink;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+static COUNT: AtomicUsize = AtomicUsize::new(0);
+
+/// Fake procfs root for tests, removed on drop
+pub struct FakeProc {
+    pub root: PathBuf,
+}
+
+impl FakeProc {
+    pub fn new() -> Self {
+        let root = std::env::temp_dir().join(format!(
+            "amdgpu_top-proc-{}-{}",
+            std::process::id(),
+            COUNT.fetch_add(1, Ordering::Relaxed),
+        ));
+        fs::create_dir_all(&root).unwrap();
+
+        Self { root }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.root
+    }
+
+    /// Creates "<pid>/comm", "<pid>/fd/" and "<pid>/fdinfo/"
+    pub fn add_proc(&self, pid: i32, name: &str) {
+        let dir = self.root.join(pid.to_string());
+        fs::create_dir_all(dir.join("fd")).unwrap();
+        fs::create_dir_all(dir.join("fdinfo")).unwrap();
+        fs::write(dir.join("comm"), format!("{name}\n")).unwrap();
+    }
+
+    /// Links "<pid>/fd/<fd>" to `target` and writes "<pid>/fdinfo/<fd>"
+    pub fn add_fd(&self, pid: i32, fd: i32, target: &str, fdinfo: &str) {
+        symlink(target, self.root.join(format!("{pid}/fd/{fd}"))).unwrap();
+        self.write_fdinfo(pid, fd, fdinfo);
+    }
+
+    pub fn remove_fd(&self, pid: i32, fd: i32) {
+        fs::remove_file(self.root.join(format!("{pid}/fd/{fd}"))).unwrap();
+        fs::remove_file(self.root.join(format!("{pid}/fdinfo/{fd}"))).unwrap();
+    }
+
+    pub fn remove_proc(&self, pid: i32) {
+        fs::remove_dir_all(self.root.join(pid.to_string())).unwrap();
+    }
+
+    pub fn write_fdinfo(&self, pid: i32, fd: i32, fdinfo: &str) {
+        fs::write(self.root.join(format!("{pid}/fdinfo/{fd}")), fdinfo).unwrap();
+    }
+}
+
+impl Drop for FakeProc {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.root);
+    }
+}
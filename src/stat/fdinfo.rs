@@ -1,72 +1,285 @@
 use std::fs;
 use std::io::Read;
 use std::fmt::{self, Write};
-use super::{Text, Opt};
+use std::path::{Path, PathBuf};
+use super::{Text, Opt, ProcFilter, read_passwd, EngineTime, ProcHistory, fmt_duration, NsInfo};
+use super::{KfdCollector, KfdUsage, LeakDetector};
 // use std::sync::{Arc, Mutex};
-use std::collections::{HashMap, HashSet};
-use std::time::Duration;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::time::{Duration, Instant};
 use serde_json::{json, Map, Value};
+use cursive::views::{Dialog, EditView};
+use cursive::view::Resizable;
 
 /// ref: drivers/gpu/drm/amd/amdgpu/amdgpu_fdinfo.c
+/// ref: Documentation/gpu/drm-usage-stats.rst
 
 const PROC_NAME_LEN: usize = 15;
+/// max number of exited processes kept in the ledger
+const LEDGER_LEN: usize = 1000;
+const USER_NAME_LEN: usize = 8;
+const NS_PID_LEN: usize = 7;
 
+pub const PROC_ROOT: &str = "/proc";
+
+const NS_PID_LABEL: &str = "NSPID";
+const USER_LABEL: &str = "USER";
 const VRAM_LABEL: &str = "VRAM";
 const GFX_LABEL: &str = "GFX";
 const COMPUTE_LABEL: &str = "Compute";
 const DMA_LABEL: &str = "DMA";
 const DEC_LABEL: &str = "DEC";
 const ENC_LABEL: &str = "ENC";
+const ENGINE_LABELS: [&str; 5] = [GFX_LABEL, COMPUTE_LABEL, DMA_LABEL, DEC_LABEL, ENC_LABEL];
+/// e.g. "100.0"
+const PERCENT_WIDTH: usize = 5;
 // const UVD_ENC_LABEL: &str = "UVD (ENC)";
 // const JPEG_LABEL: &str = "JPEG";
 
 #[derive(Debug, Default, Clone)]
 pub struct ProcInfo {
-    pid: i32,
-    name: String,
-    fds: Vec<i32>,
+    pub pid: i32,
+    pub name: String,
+    pub fds: Vec<i32>,
+    pub cgroup: String,
+    pub uid: Option<u32>,
+    pub ns: NsInfo,
 }
 
 impl ProcInfo {
-    pub fn from_pid(pid: i32, target_device: &str) -> Self {
-        let mut name = fs::read_to_string(format!("/proc/{pid}/comm")).unwrap();
+    pub fn from_pid(pid: i32, proc_root: &Path, device_nodes: &[String]) -> Self {
+        let mut name = fs::read_to_string(proc_root.join(format!("{pid}/comm"))).unwrap();
         name.pop(); // trim '\n'
 
         Self {
             pid,
             name,
-            fds: get_fds(pid, target_device),
+            fds: get_fds(pid, proc_root, device_nodes),
+            cgroup: get_cgroup(pid, proc_root),
+            uid: get_uid(pid, proc_root),
+            ns: NsInfo::from_pid(pid, proc_root),
+        }
+    }
+
+    /// `false` if the process has exited, or the PID is reused by a process with another name
+    pub fn is_alive(&self, proc_root: &Path) -> bool {
+        fs::read_to_string(proc_root.join(format!("{}/comm", self.pid)))
+            .map(|comm| comm.trim_end_matches('\n') == self.name)
+            .unwrap_or(false)
+    }
+
+    /// `None` if the process has exited
+    pub fn with_fds(pid: i32, proc_root: &Path, fds: Vec<i32>) -> Option<Self> {
+        let mut name = fs::read_to_string(proc_root.join(format!("{pid}/comm"))).ok()?;
+        name.pop(); // trim '\n'
+
+        Some(Self {
+            pid,
+            name,
+            fds,
+            cgroup: get_cgroup(pid, proc_root),
+            uid: get_uid(pid, proc_root),
+            ns: NsInfo::from_pid(pid, proc_root),
+        })
+    }
+}
+
+/// Memory regions of amdgpu, KiB
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, PartialOrd)]
+pub struct MemRegions {
+    pub vram: u64,
+    pub gtt: u64,
+    pub cpu: u64,
+}
+
+impl MemRegions {
+    fn get_mut(&mut self, region: &str) -> Option<&mut u64> {
+        match region {
+            "vram" => Some(&mut self.vram),
+            "gtt" => Some(&mut self.gtt),
+            "cpu" => Some(&mut self.cpu),
+            _ => None,
+        }
+    }
+}
+
+impl std::ops::AddAssign for MemRegions {
+    fn add_assign(&mut self, other: Self) {
+        self.vram += other.vram;
+        self.gtt += other.gtt;
+        self.cpu += other.cpu;
+    }
+}
+
+/// Per-engine values of `drm-engine-capacity-*`, `drm-cycles-*` and `drm-maxfreq-*`
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, PartialOrd)]
+pub struct EngineValues {
+    pub gfx: u64,
+    pub compute: u64,
+    pub dma: u64,
+    pub dec: u64,
+    pub enc: u64,
+    pub uvd_enc: u64,
+    pub vcn_jpeg: u64,
+}
+
+impl EngineValues {
+    fn get_mut(&mut self, engine: &str) -> Option<&mut u64> {
+        match engine {
+            "gfx" => Some(&mut self.gfx),
+            "compute" => Some(&mut self.compute),
+            "dma" => Some(&mut self.dma),
+            "dec" => Some(&mut self.dec),
+            "enc" => Some(&mut self.enc),
+            "enc_1" => Some(&mut self.uvd_enc),
+            "jpeg" => Some(&mut self.vcn_jpeg),
+            _ => None,
         }
     }
+
+    fn zip_mut(&mut self, other: &Self) -> [(&mut u64, u64); 7] {
+        [
+            (&mut self.gfx, other.gfx),
+            (&mut self.compute, other.compute),
+            (&mut self.dma, other.dma),
+            (&mut self.dec, other.dec),
+            (&mut self.enc, other.enc),
+            (&mut self.uvd_enc, other.uvd_enc),
+            (&mut self.vcn_jpeg, other.vcn_jpeg),
+        ]
+    }
 }
 
 #[derive(Clone, Debug, Default, Eq, PartialEq, PartialOrd)]
 pub struct FdInfoUsage {
     // client_id: usize,
-    vram_usage: u64, // KiB
-    gtt_usage: u64, // KiB
-    cpu_accessible_usage: u64, // KiB
-    gfx: i64,
-    compute: i64,
-    dma: i64,
-    dec: i64,
-    enc: i64,
-    uvd_enc: i64,
-    vcn_jpeg: i64,
+    pub vram_usage: u64, // KiB, drm-memory-vram or drm-resident-vram
+    pub gtt_usage: u64, // KiB
+    pub cpu_accessible_usage: u64, // KiB
+    pub total: MemRegions,
+    pub shared: MemRegions,
+    pub active: MemRegions,
+    pub purgeable: MemRegions,
+    pub gfx: i64, // ns
+    pub compute: i64,
+    pub dma: i64,
+    pub dec: i64,
+    pub enc: i64,
+    pub uvd_enc: i64,
+    pub vcn_jpeg: i64,
+    pub engine_capacity: EngineValues,
+    pub cycles: EngineValues,
+    pub maxfreq: EngineValues, // MHz
 }
 
-#[derive(Clone, Debug, Default, Eq, PartialEq, PartialOrd)]
+/// Engine usage between two reads of fdinfo, %
+#[derive(Clone, Copy, Debug, Default, PartialEq, PartialOrd)]
+pub struct EngineUsage {
+    pub gfx: f64,
+    pub compute: f64,
+    pub dma: f64,
+    pub dec: f64,
+    pub enc: f64,
+    pub uvd_enc: f64,
+    pub vcn_jpeg: f64,
+}
+
+impl EngineUsage {
+    /// "enc" and "enc_1" are rings of the same encoder, so the sum is clamped to 100%.
+    pub fn enc_total(&self) -> f64 {
+        (self.enc + self.uvd_enc).min(100.0)
+    }
+
+    pub fn media(&self) -> f64 {
+        self.dec + self.enc_total() + self.vcn_jpeg
+    }
+
+    /// Sums of several processes can exceed 100%
+    fn clamp(&self) -> Self {
+        let f = |usage: f64| usage.min(100.0);
+
+        Self {
+            gfx: f(self.gfx),
+            compute: f(self.compute),
+            dma: f(self.dma),
+            dec: f(self.dec),
+            enc: f(self.enc),
+            uvd_enc: f(self.uvd_enc),
+            vcn_jpeg: f(self.vcn_jpeg),
+        }
+    }
+}
+
+impl std::ops::AddAssign for EngineUsage {
+    fn add_assign(&mut self, other: Self) {
+        self.gfx += other.gfx;
+        self.compute += other.compute;
+        self.dma += other.dma;
+        self.dec += other.dec;
+        self.enc += other.enc;
+        self.uvd_enc += other.uvd_enc;
+        self.vcn_jpeg += other.vcn_jpeg;
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, PartialOrd)]
+pub struct ClientUsage {
+    client_id: usize,
+    usage: FdInfoUsage,
+    engine: EngineUsage,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, PartialOrd)]
 pub struct ProcUsage {
     pid: i32,
     name: String,
+    cgroup: String,
+    user: String,
+    ns: NsInfo,
+    usage: FdInfoUsage,
+    engine: EngineUsage,
+    clients: Vec<ClientUsage>,
+    kfd: Option<KfdUsage>,
+    kfd_sdma: f64, // %
+    leak_rate: Option<f64>, // KiB/s
+}
+
+/// Sum of `ProcUsage` per group (e.g. cgroup path, user)
+#[derive(Clone, Debug, Default, PartialEq, PartialOrd)]
+pub struct GroupUsage {
+    name: String,
+    pids: Vec<i32>,
     usage: FdInfoUsage,
+    engine: EngineUsage,
 }
 
+/// The previous stat and when it was read
+type PreStat = (FdInfoUsage, Instant);
+
 #[derive(Default)]
 pub struct FdInfoView {
-    pid_map: HashMap<i32, FdInfoUsage>,
+    pid_map: HashMap<i32, PreStat>,
+    client_map: HashMap<(i32, usize), PreStat>,
     pub proc_usage: Vec<ProcUsage>,
-    pub interval: Duration,
+    /// engine usage summed over all processes, regardless of `filter`
+    total_engine: EngineUsage,
+    pub filter: Option<ProcFilter>,
+    pub kfd: Option<KfdCollector>,
+    /// the previous SDMA time of KFD processes and when it was read
+    kfd_map: HashMap<i32, (u64, Instant)>,
+    /// enabled by `--leak-window`
+    pub leak: Option<LeakDetector>,
+    proc_root: PathBuf,
+    user_names: HashMap<u32, String>,
+    history: HashMap<i32, ProcHistory>,
+    /// PID of the row selected with Ctrl-N/Ctrl-P, marked with '>'
+    pub selected: Option<i32>,
+    /// PID and user name of the rows of the process table, in the displayed order.
+    /// Empty if the table is grouped by cgroup or user.
+    pub rows: Vec<(i32, String)>,
+    /// exited processes, oldest first
+    pub exited: VecDeque<ProcHistory>,
+    pub ledger_text: Text,
     pub text: Text,
 }
 
@@ -78,10 +291,17 @@ pub enum FdInfoSortType {
     MediaEngine,
 }
 
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum FdInfoGroupType {
+    Process,
+    Cgroup,
+    User,
+}
+
 impl FdInfoView {
-    pub fn new(interval: Duration) -> Self {
+    pub fn new(proc_root: &Path) -> Self {
         Self {
-            interval,
+            proc_root: proc_root.to_path_buf(),
             ..Default::default()
         }
     }
@@ -90,71 +310,197 @@ impl FdInfoView {
         &mut self,
         slice_proc_info: &[ProcInfo],
         sort: &FdInfoSortType,
-        reverse: bool
+        reverse: bool,
+        clients: bool,
+        group: &FdInfoGroupType,
     ) -> Result<(), fmt::Error> {
         self.text.clear();
-        self.proc_usage.clear();
+        self.clear_usage();
 
-        writeln!(
+        write!(
             self.text.buf,
-            " {pad:26} | {VRAM_LABEL:^8} | {GFX_LABEL} | {COMPUTE_LABEL} | {DMA_LABEL} | {DEC_LABEL} | {ENC_LABEL} |",
+            " {pad:26} | {NS_PID_LABEL:>NS_PID_LEN$} | {USER_LABEL:USER_NAME_LEN$} | {VRAM_LABEL:^8} |",
             pad = "",
         )?;
+        for label in ENGINE_LABELS {
+            write!(self.text.buf, " {label:^width$} |", width = label.len().max(PERCENT_WIDTH))?;
+        }
+        writeln!(self.text.buf)?;
+
+        if let Some(filter) = &self.filter {
+            writeln!(self.text.buf, " filter: {filter}")?;
+        }
 
         for proc_info in slice_proc_info {
             self.get_proc_usage(proc_info);
         }
 
+        self.update_exited(slice_proc_info);
+
         self.proc_usage.sort_by(|a, b|
             match (sort, reverse) {
                 (FdInfoSortType::PID, false) => b.pid.cmp(&a.pid),
                 (FdInfoSortType::PID, true) => a.pid.cmp(&b.pid),
                 (FdInfoSortType::VRAM, false) => b.usage.vram_usage.cmp(&a.usage.vram_usage),
                 (FdInfoSortType::VRAM, true) => a.usage.vram_usage.cmp(&b.usage.vram_usage),
-                (FdInfoSortType::GFX, false) => b.usage.gfx.cmp(&a.usage.gfx),
-                (FdInfoSortType::GFX, true) => a.usage.gfx.cmp(&b.usage.gfx),
+                (FdInfoSortType::GFX, false) => b.engine.gfx.total_cmp(&a.engine.gfx),
+                (FdInfoSortType::GFX, true) => a.engine.gfx.total_cmp(&b.engine.gfx),
+                (FdInfoSortType::MediaEngine, false) =>
+                    b.engine.media().total_cmp(&a.engine.media()),
+                (FdInfoSortType::MediaEngine, true) =>
+                    a.engine.media().total_cmp(&b.engine.media()),
+            }
+        );
+
+        self.rows = match group {
+            FdInfoGroupType::Process => self.proc_usage.iter().map(|pu| (pu.pid, pu.user.clone())).collect(),
+            FdInfoGroupType::Cgroup |
+            FdInfoGroupType::User => Vec::new(),
+        };
+
+        match group {
+            FdInfoGroupType::Process => self.print_usage(clients)?,
+            FdInfoGroupType::Cgroup |
+            FdInfoGroupType::User => self.print_group_usage(group, sort, reverse)?,
+        }
+
+        Ok(())
+    }
+
+    /// Sums `proc_usage` per group, processes without group are ignored.
+    pub fn group_usage(&self, group: &FdInfoGroupType) -> Vec<GroupUsage> {
+        let mut vec_group: Vec<GroupUsage> = Vec::new();
+
+        for pu in &self.proc_usage {
+            let name = match group {
+                FdInfoGroupType::Process => continue,
+                FdInfoGroupType::Cgroup => &pu.cgroup,
+                FdInfoGroupType::User => &pu.user,
+            };
+
+            if let Some(gu) = vec_group.iter_mut().find(|gu| &gu.name == name) {
+                gu.pids.push(pu.pid);
+                gu.usage += pu.usage.clone();
+                gu.engine += pu.engine;
+            } else {
+                vec_group.push(GroupUsage {
+                    name: name.to_string(),
+                    pids: vec![pu.pid],
+                    usage: pu.usage.clone(),
+                    engine: pu.engine,
+                });
+            }
+        }
+
+        for gu in vec_group.iter_mut() {
+            gu.engine = gu.engine.clamp();
+        }
+
+        vec_group
+    }
+
+    pub fn print_group_usage(
+        &mut self,
+        group: &FdInfoGroupType,
+        sort: &FdInfoSortType,
+        reverse: bool,
+    ) -> Result<(), fmt::Error> {
+        const GROUP_NAME_LEN: usize = 20;
+        let mut vec_group = self.group_usage(group);
+
+        vec_group.sort_by(|a, b|
+            match (sort, reverse) {
+                (FdInfoSortType::PID, false) => a.name.cmp(&b.name),
+                (FdInfoSortType::PID, true) => b.name.cmp(&a.name),
+                (FdInfoSortType::VRAM, false) => b.usage.vram_usage.cmp(&a.usage.vram_usage),
+                (FdInfoSortType::VRAM, true) => a.usage.vram_usage.cmp(&b.usage.vram_usage),
+                (FdInfoSortType::GFX, false) => b.engine.gfx.total_cmp(&a.engine.gfx),
+                (FdInfoSortType::GFX, true) => a.engine.gfx.total_cmp(&b.engine.gfx),
                 (FdInfoSortType::MediaEngine, false) =>
-                    (b.usage.dec + b.usage.enc + b.usage.uvd_enc)
-                        .cmp(&(a.usage.dec + a.usage.enc + a.usage.uvd_enc)),
+                    b.engine.media().total_cmp(&a.engine.media()),
                 (FdInfoSortType::MediaEngine, true) =>
-                    (a.usage.dec + a.usage.enc + a.usage.uvd_enc)
-                        .cmp(&(b.usage.dec + b.usage.enc + b.usage.uvd_enc)),
+                    a.engine.media().total_cmp(&b.engine.media()),
             }
         );
 
-        self.print_usage()?;
+        for gu in &vec_group {
+            write!(
+                self.text.buf,
+                " {name:GROUP_NAME_LEN$} ({procs:>3}) | {pad:NS_PID_LEN$} | {pad:USER_NAME_LEN$} |",
+                // the last part of the cgroup path is the most specific one
+                name = truncate_head(&gu.name, GROUP_NAME_LEN),
+                procs = gu.pids.len(),
+                pad = "",
+            )?;
+            write_usage(&mut self.text.buf, &gu.usage, &gu.engine)?;
+        }
 
         Ok(())
     }
 
-    pub fn print_usage(&mut self) -> Result<(), fmt::Error> {
+    /// `clients`: also print one row per `drm-client-id` under each process
+    pub fn print_usage(&mut self, clients: bool) -> Result<(), fmt::Error> {
         for pu in &self.proc_usage {
             write!(
                 self.text.buf,
-                " {name:PROC_NAME_LEN$} ({pid:>8}) | {vram:>5} MiB|",
+                "{mark}{name:PROC_NAME_LEN$} ({pid:>8}) | {ns_pid:>NS_PID_LEN$} | {user:USER_NAME_LEN$} |",
+                mark = if self.selected == Some(pu.pid) { '>' } else { ' ' },
                 name = pu.name,
                 pid = pu.pid,
-                vram = pu.usage.vram_usage >> 10,
+                ns_pid = pu.ns.inner_pid().map(|p| p.to_string()).unwrap_or_default(),
+                user = pu.user.chars().take(USER_NAME_LEN).collect::<String>(),
             )?;
-            let enc_usage = pu.usage.enc + pu.usage.uvd_enc;
-            for (usage, label_len) in [
-                (pu.usage.gfx, GFX_LABEL.len()),
-                (pu.usage.compute, COMPUTE_LABEL.len()),
-                (pu.usage.dma, DMA_LABEL.len()),
-                (pu.usage.dec, DEC_LABEL.len()), // UVD/VCN
-                (enc_usage, ENC_LABEL.len()),
-                // (enc, ENC_LABEL), // VCE/VCN
-                // (uvd_enc, UVD_ENC_LABEL), // UVD
-                // (vcn_jpeg, JPEG_LABEL) // VCN
-            ] {
-                write!(self.text.buf, " {usage:>label_len$}%|")?;
+            write_usage(&mut self.text.buf, &pu.usage, &pu.engine)?;
+
+            if let Some(kfd) = &pu.kfd {
+                writeln!(
+                    self.text.buf,
+                    " {label:>PROC_NAME_LEN$} {pad:10} | {pad:NS_PID_LEN$} | {pad:USER_NAME_LEN$} | {vram:>5} MiB| SDMA {sdma:.1}%, CU {cu}",
+                    label = "KFD",
+                    pad = "",
+                    vram = kfd.vram_usage >> 10,
+                    sdma = pu.kfd_sdma,
+                    cu = kfd.cu_occupancy.map(|v| v.to_string()).unwrap_or_else(|| "-".to_string()),
+                )?;
+            }
+
+            if let Some(rate) = pu.leak_rate {
+                writeln!(
+                    self.text.buf,
+                    " {label:>PROC_NAME_LEN$} {pad:10} | {pad:NS_PID_LEN$} | {pad:USER_NAME_LEN$} | VRAM+GTT rising steadily, +{rate:.1} MiB/min",
+                    label = "LEAK?",
+                    pad = "",
+                    rate = rate * 60.0 / 1024.0,
+                )?;
+            }
+
+            if !clients { continue }
+
+            for cu in &pu.clients {
+                write!(
+                    self.text.buf,
+                    " {label:>PROC_NAME_LEN$} ({id:>8}) | {pad:NS_PID_LEN$} | {pad:USER_NAME_LEN$} |",
+                    label = "client_id",
+                    id = cu.client_id,
+                    pad = "",
+                )?;
+                write_usage(&mut self.text.buf, &cu.usage, &cu.engine)?;
             }
-            writeln!(self.text.buf)?;
         }
 
         Ok(())
     }
 
+    pub fn clear_usage(&mut self) {
+        self.proc_usage.clear();
+        self.total_engine = EngineUsage::default();
+    }
+
+    /// System-wide engine usage, normalized by `drm-engine-capacity-*` (0-100%)
+    pub fn engines(&self) -> EngineUsage {
+        self.total_engine.clamp()
+    }
+
     pub fn get_proc_usage(&mut self, proc_info: &ProcInfo) {
         let pid = proc_info.pid;
         let name = if PROC_NAME_LEN < proc_info.name.len() {
@@ -164,102 +510,204 @@ impl FdInfoView {
         };
         let mut ids = HashSet::<usize>::new();
         let mut stat = FdInfoUsage::default();
+        let mut clients: Vec<(usize, FdInfoUsage, Instant)> = Vec::new();
         let mut buf = String::new();
 
-        'fds: for fd in &proc_info.fds {
-            let path = format!("/proc/{pid}/fdinfo/{fd}");
-            let Ok(mut f) = fs::File::open(&path) else { continue };
-            if f.read_to_string(&mut buf).is_err() { continue }
-            let mut lines = buf.lines();
+        for fd in &proc_info.fds {
+            let path = self.proc_root.join(format!("{pid}/fdinfo/{fd}"));
+            let Some((id, client_stat)) = read_fdinfo(&path, &mut buf) else { continue };
+            // dup'ed fds share the same DRM client
+            if !ids.insert(id) { continue }
 
-            'fdinfo: loop {
-                let Some(l) = lines.next() else { break 'fdinfo; };
+            stat += client_stat.clone();
+            clients.push((id, client_stat, Instant::now()));
+        }
 
-                if l.starts_with("drm-client-id") {
-                    let id = FdInfoUsage::id_parse(l);
-                    if !ids.insert(id) { continue 'fds; }
-                    continue 'fdinfo;
-                }
-                if l.starts_with("drm-memory") {
-                    stat.mem_usage_parse(l);
-                    continue 'fdinfo;
-                }
-                if l.starts_with("drm-engine") {
-                    stat.engine_parse(l);
-                    continue 'fdinfo;
-                }
-            } // 'fdinfo
-        } // 'fds
+        let now = Instant::now();
 
-        let diff = {
-            if let Some(pre_stat) = self.pid_map.get_mut(&pid) {
-                let tmp = stat.calc_usage(pre_stat, &self.interval);
-                *pre_stat = stat;
+        {
+            let engine = self.pid_map.get(&pid)
+                .map(|(pre_stat, _)| EngineTime::from_delta(&stat, pre_stat))
+                .unwrap_or_default();
+
+            self.history
+                .entry(pid)
+                .or_insert_with(|| ProcHistory::new(pid, &proc_info.name, now))
+                .update(engine, stat.vram_usage, now);
+        }
 
-                tmp
-            } else {
-                let [vram_usage, gtt_usage, cpu_accessible_usage] = [
-                    stat.vram_usage,
-                    stat.gtt_usage,
-                    stat.cpu_accessible_usage,
-                ];
-
-                self.pid_map.insert(pid, stat);
-
-                FdInfoUsage {
-                    vram_usage,
-                    gtt_usage,
-                    cpu_accessible_usage,
-                    ..Default::default()
-                }
+        let engine = diff_usage(&mut self.pid_map, pid, &stat, now);
+
+        let mut clients: Vec<ClientUsage> = clients.into_iter().map(|(client_id, stat, time)| {
+            ClientUsage {
+                client_id,
+                engine: diff_usage(&mut self.client_map, (pid, client_id), &stat, time),
+                usage: stat,
             }
-        };
+        }).collect();
+        clients.sort_by_key(|cu| cu.client_id);
+
+        let kfd = self.kfd.as_ref().and_then(|kfd| kfd.read(pid));
+        let kfd_sdma = kfd.map(|kfd| self.diff_kfd_sdma(pid, kfd.sdma, now)).unwrap_or(0.0);
+
+        let leak_rate = self.leak.as_mut().and_then(|leak| {
+            leak.update(pid, stat.vram_usage, stat.gtt_usage, now);
+            leak.growth_rate(pid)
+        });
+
+        self.total_engine += engine;
+
+        if let Some(filter) = &self.filter {
+            if !filter.is_match(proc_info) { return }
+        }
+
+        let user = self.get_user_name(proc_info.uid);
 
         self.proc_usage.push(ProcUsage {
             pid,
             name: name.to_string(),
-            usage: diff
+            cgroup: proc_info.cgroup.clone(),
+            user,
+            ns: proc_info.ns.clone(),
+            usage: stat,
+            engine,
+            clients,
+            kfd,
+            kfd_sdma,
+            leak_rate,
         });
     }
 
-    pub fn json_value(&self) -> Value {
-        let Some(pu) = self.proc_usage.get(0) else { return Value::Null };
-        let mut m = Map::new();
+    /// Moves processes that are no longer in `slice_proc_info` to the ledger of exited processes.
+    pub fn update_exited(&mut self, slice_proc_info: &[ProcInfo]) {
+        let alive: HashSet<i32> = slice_proc_info.iter().map(|p| p.pid).collect();
+        let exited_pids: Vec<i32> = self.history.keys()
+            .filter(|pid| !alive.contains(pid))
+            .copied()
+            .collect();
 
-        for (usage, label) in [
-            (pu.usage.vram_usage >> 10, "VRAM Usage"),
-            (pu.usage.gtt_usage >> 10, "GTT Usage"),
-        ] {
-            m.insert(
-                label.to_string(),
-                json!({
-                    "value": usage,
-                    "unit": "MiB",
-                }),
-            );
-        }
-
-        let enc_usage = pu.usage.enc + pu.usage.uvd_enc;
-        for (usage, label) in [
-            (pu.usage.gfx, GFX_LABEL),
-            (pu.usage.compute, COMPUTE_LABEL),
-            (pu.usage.dma, DMA_LABEL),
-            (pu.usage.dec, DEC_LABEL), // UVD/VCN
-            (enc_usage, ENC_LABEL),
-            // (enc, ENC_LABEL), // VCE/VCN
-            // (uvd_enc, UVD_ENC_LABEL), // UVD
-            // (vcn_jpeg, JPEG_LABEL) // VCN
+        for pid in &exited_pids {
+            let Some(hist) = self.history.remove(pid) else { continue };
+
+            if LEDGER_LEN <= self.exited.len() {
+                self.exited.pop_front();
+            }
+            self.exited.push_back(hist);
+        }
+
+        self.pid_map.retain(|pid, _| alive.contains(pid));
+        self.client_map.retain(|(pid, _), _| alive.contains(pid));
+        self.kfd_map.retain(|pid, _| alive.contains(pid));
+        if let Some(leak) = &mut self.leak {
+            leak.retain(&alive);
+        }
+    }
+
+    pub fn print_ledger(&mut self) -> Result<(), fmt::Error> {
+        const ROWS: usize = 10;
+
+        self.ledger_text.clear();
+
+        writeln!(
+            self.ledger_text.buf,
+            " {pad:26} | Lifetime | {GFX_LABEL:>8} | {COMPUTE_LABEL:>8} | {DMA_LABEL:>8} | Media    | Peak VRAM |",
+            pad = "",
+        )?;
+
+        let mut running: Vec<&ProcHistory> = self.history.values().collect();
+        running.sort_by_key(|hist| std::cmp::Reverse(hist.engine.gfx));
+
+        for (label, list) in [
+            ("Running:", running),
+            ("Exited:", self.exited.iter().rev().take(ROWS).collect()),
         ] {
-            m.insert(
-                label.to_string(),
-                json!({
-                    "value": usage,
-                    "unit": "%",
-                }),
-            );
+            if list.is_empty() { continue }
+
+            writeln!(self.ledger_text.buf, " {label}")?;
+
+            for hist in list.iter().take(ROWS) {
+                write!(
+                    self.ledger_text.buf,
+                    " {name:PROC_NAME_LEN$} ({pid:>8}) | {lifetime} |",
+                    name = hist.name.chars().take(PROC_NAME_LEN).collect::<String>(),
+                    pid = hist.pid,
+                    lifetime = fmt_duration(hist.lifetime()),
+                )?;
+
+                for ns in [hist.engine.gfx, hist.engine.compute, hist.engine.dma, hist.engine.media()] {
+                    let sec = ns as f64 / 1_000_000_000.0;
+                    write!(self.ledger_text.buf, " {sec:>7.1}s |")?;
+                }
+
+                writeln!(self.ledger_text.buf, " {:>5} MiB |", hist.peak_vram >> 10)?;
+            }
         }
 
-        m.into()
+        Ok(())
+    }
+
+    pub fn json_ledger_value(&self) -> Value {
+        let exited: Vec<Value> = self.exited.iter().map(|hist| hist.json_value()).collect();
+        let running: Vec<Value> = self.history.values().map(|hist| hist.json_value()).collect();
+
+        json!({
+            "exited": exited,
+            "running": running,
+        })
+    }
+
+    /// SDMA usage of the KFD process since the previous read, %
+    /// `sdma` is the cumulative SDMA time in microseconds, 0% for the first read.
+    fn diff_kfd_sdma(&mut self, pid: i32, sdma: u64, now: Instant) -> f64 {
+        let Some((pre, pre_time)) = self.kfd_map.insert(pid, (sdma, now)) else { return 0.0 };
+        let elapsed = now.duration_since(pre_time).as_micros() as f64;
+
+        if elapsed == 0.0 { return 0.0 }
+
+        sdma.saturating_sub(pre) as f64 * 100.0 / elapsed
+    }
+
+    /// Resolves UID through "/etc/passwd", the UID is returned as is if no entry is found.
+    fn get_user_name(&mut self, uid: Option<u32>) -> String {
+        let Some(uid) = uid else { return String::new() };
+
+        if !self.user_names.contains_key(&uid) {
+            self.user_names.extend(read_passwd().into_iter().map(|(name, uid)| (uid, name)));
+            // UIDs without an entry are cached too, so "/etc/passwd" is not read on every update
+            self.user_names.entry(uid).or_insert_with(|| uid.to_string());
+        }
+
+        self.user_names[&uid].clone()
+    }
+
+    pub fn json_value(&self) -> Value {
+        let Some(pu) = self.proc_usage.get(0) else { return Value::Null };
+
+        proc_json_map(pu).into()
+    }
+
+    pub fn json_all_value(&self) -> Value {
+        let procs: Vec<Value> = self.proc_usage.iter().map(|pu| {
+            let mut m = proc_json_map(pu);
+            m.insert("pid".to_string(), pu.pid.into());
+            m.insert("name".to_string(), pu.name.clone().into());
+
+            m.into()
+        }).collect();
+
+        procs.into()
+    }
+
+    pub fn json_group_value(&self, group: &FdInfoGroupType) -> Value {
+        let groups: Vec<Value> = self.group_usage(group).iter().map(|gu| {
+            let mut m = usage_json_map(&gu.usage, &gu.engine);
+            m.insert("name".to_string(), gu.name.clone().into());
+            m.insert("pids".to_string(), gu.pids.clone().into());
+
+            m.into()
+        }).collect();
+
+        groups.into()
     }
 
     pub fn cb(siv: &mut cursive::Cursive) {
@@ -269,6 +717,91 @@ impl FdInfoView {
         }
     }
 
+    pub fn cb_clients(siv: &mut cursive::Cursive) {
+        {
+            let mut opt = siv.user_data::<Opt>().unwrap().lock().unwrap();
+            opt.fdinfo_clients ^= true;
+        }
+    }
+
+    fn toggle_group(siv: &mut cursive::Cursive, group: FdInfoGroupType) {
+        {
+            let mut opt = siv.user_data::<Opt>().unwrap().lock().unwrap();
+            opt.fdinfo_group = if opt.fdinfo_group == group {
+                FdInfoGroupType::Process
+            } else {
+                group
+            };
+        }
+    }
+
+    pub fn cb_group_by_cgroup(siv: &mut cursive::Cursive) {
+        Self::toggle_group(siv, FdInfoGroupType::Cgroup);
+    }
+
+    pub fn cb_group_by_user(siv: &mut cursive::Cursive) {
+        Self::toggle_group(siv, FdInfoGroupType::User);
+    }
+
+    pub fn cb_filter(siv: &mut cursive::Cursive) {
+        let cur = {
+            let opt = siv.user_data::<Opt>().unwrap().lock().unwrap();
+            opt.fdinfo_filter.as_ref().map(|f| f.to_string()).unwrap_or_default()
+        };
+
+        let edit = EditView::new()
+            .content(cur)
+            .on_submit(|siv: &mut cursive::Cursive, s: &str| {
+                let filter = if s.trim().is_empty() {
+                    None
+                } else {
+                    match s.parse::<ProcFilter>() {
+                        Ok(f) => Some(f),
+                        Err(err) => {
+                            siv.add_layer(Dialog::info(err));
+                            return;
+                        },
+                    }
+                };
+
+                {
+                    let mut opt = siv.user_data::<Opt>().unwrap().lock().unwrap();
+                    opt.fdinfo_filter = filter;
+                }
+
+                siv.pop_layer();
+            });
+
+        siv.add_layer(
+            Dialog::around(edit.fixed_width(40))
+                .title("fdinfo filter (name, pid:<PID>, user:<name|UID>)")
+                .dismiss_button("Cancel")
+        );
+    }
+
+    fn move_selection(siv: &mut cursive::Cursive, step: isize) {
+        {
+            let mut opt = siv.user_data::<Opt>().unwrap().lock().unwrap();
+            let pids: Vec<i32> = opt.fdinfo_rows.iter().map(|(pid, _)| *pid).collect();
+            opt.fdinfo_selected = next_selection(&pids, opt.fdinfo_selected, step);
+        }
+    }
+
+    pub fn cb_select_next(siv: &mut cursive::Cursive) {
+        Self::move_selection(siv, 1);
+    }
+
+    pub fn cb_select_prev(siv: &mut cursive::Cursive) {
+        Self::move_selection(siv, -1);
+    }
+
+    pub fn cb_ledger(siv: &mut cursive::Cursive) {
+        {
+            let mut opt = siv.user_data::<Opt>().unwrap().lock().unwrap();
+            opt.fdinfo_ledger ^= true;
+        }
+    }
+
     pub fn cb_reverse_sort(siv: &mut cursive::Cursive) {
         {
             let mut opt = siv.user_data::<Opt>().unwrap().lock().unwrap();
@@ -305,105 +838,339 @@ impl FdInfoView {
     }
 }
 
-impl FdInfoUsage {
-    fn id_parse(s: &str) -> usize {
-        const LEN: usize = "drm-client-id:\t".len();
-        s[LEN..].parse().unwrap()
-    }
-
-    fn mem_usage_parse(&mut self, s: &str) {
-        const PRE: usize = "drm-memory-xxxx:\t".len(); // "vram:" or "gtt: " or "cpu: "
-        const KIB: usize = " KiB".len();
-        let len = s.len();
-
-        const MEM_TYPE: std::ops::Range<usize> = {
-            const PRE_LEN: usize = "drm-memory-".len();
-
-            PRE_LEN..(PRE_LEN+5)
-        };
-
-        let usage = s[PRE..(len-KIB)].parse().unwrap_or(0);
-
-        match &s[MEM_TYPE] {
-            "vram:" => self.vram_usage += usage,
-            "gtt: " => self.gtt_usage += usage,
-            "cpu: " => self.cpu_accessible_usage += usage,
-            _ => {},
-        };
+impl std::ops::AddAssign for FdInfoUsage {
+    fn add_assign(&mut self, other: Self) {
+        self.vram_usage += other.vram_usage;
+        self.gtt_usage += other.gtt_usage;
+        self.cpu_accessible_usage += other.cpu_accessible_usage;
+        self.total += other.total;
+        self.shared += other.shared;
+        self.active += other.active;
+        self.purgeable += other.purgeable;
+        self.gfx += other.gfx;
+        self.compute += other.compute;
+        self.dma += other.dma;
+        self.dec += other.dec;
+        self.enc += other.enc;
+        self.uvd_enc += other.uvd_enc;
+        self.vcn_jpeg += other.vcn_jpeg;
+
+        for (dst, src) in self.engine_capacity.zip_mut(&other.engine_capacity) {
+            *dst = (*dst).max(src);
+        }
+        for (dst, src) in self.cycles.zip_mut(&other.cycles) {
+            *dst += src;
+        }
+        for (dst, src) in self.maxfreq.zip_mut(&other.maxfreq) {
+            *dst = (*dst).max(src);
+        }
     }
+}
 
-    fn engine_parse(&mut self, s: &str) {
-        const PRE: usize = "drm-engine-".len();
-        const NS: usize = " ns".len();
-        let Some(pos) = s.find('\t') else { return };
-
-        let ns: i64 = {
-            let len = s.len();
-            s[pos+1..(len-NS)].parse().unwrap_or(0)
-        };
-
-        match &s[PRE..pos] {
-            "gfx:" => self.gfx += ns,
-            "compute:" => self.compute += ns,
-            "dma:" => self.dma += ns,
-            "dec:" => self.dec += ns,
-            "enc:" => self.enc += ns,
-            "enc_1:" => self.uvd_enc += ns,
-            "jpeg:" => self.vcn_jpeg += ns,
-            _ => {},
-        };
+impl FdInfoUsage {
+    /// Unknown keys, regions and engines are ignored.
+    fn set_value(&mut self, key: &str, val: u64, unit: Option<&str>) {
+        let Some(key) = key.strip_prefix("drm-") else { return };
+
+        // "engine-capacity-" must be checked before "engine-"
+        if let Some(engine) = key.strip_prefix("engine-capacity-") {
+            let Some(dst) = self.engine_capacity.get_mut(engine) else { return };
+            *dst = val;
+        } else if let Some(engine) = key.strip_prefix("engine-") {
+            // values out of the range of the usage counters are ignored
+            let Ok(ns) = i64::try_from(val) else { return };
+            match engine {
+                "gfx" => self.gfx = ns,
+                "compute" => self.compute = ns,
+                "dma" => self.dma = ns,
+                "dec" => self.dec = ns,
+                "enc" => self.enc = ns,
+                "enc_1" => self.uvd_enc = ns,
+                "jpeg" => self.vcn_jpeg = ns,
+                _ => {},
+            }
+        } else if let Some(engine) = key.strip_prefix("cycles-") {
+            let Some(dst) = self.cycles.get_mut(engine) else { return };
+            *dst = val;
+        } else if let Some(engine) = key.strip_prefix("maxfreq-") {
+            let Some(dst) = self.maxfreq.get_mut(engine) else { return };
+            let Some(mhz) = freq_to_mhz(val, unit) else { return };
+            *dst = mhz;
+        } else if let Some((mem_type, region)) = key.split_once('-') {
+            let dst = match mem_type {
+                // "drm-memory-*" is the legacy alias of "drm-resident-*"
+                "memory" | "resident" => match region {
+                    "vram" => Some(&mut self.vram_usage),
+                    "gtt" => Some(&mut self.gtt_usage),
+                    "cpu" => Some(&mut self.cpu_accessible_usage),
+                    _ => None,
+                },
+                "total" => self.total.get_mut(region),
+                "shared" => self.shared.get_mut(region),
+                "active" => self.active.get_mut(region),
+                "purgeable" => self.purgeable.get_mut(region),
+                _ => None,
+            };
+            let (Some(dst), Some(kib)) = (dst, mem_to_kib(val, unit)) else { return };
+            *dst = kib;
+        }
     }
 
-    pub fn calc_usage(&self, pre_stat: &Self, interval: &Duration) -> Self {
-        let [gfx, compute, dma, dec, enc, uvd_enc, _vcn_jpeg] = {
+    /// `elapsed`: measured time between the reads of `pre_stat` and `self`
+    /// Each engine is normalized by the number of rings (`drm-engine-capacity-*`, default 1),
+    /// so the usage is 0-100%.
+    pub fn calc_usage(&self, pre_stat: &Self, elapsed: &Duration) -> EngineUsage {
+        let elapsed = elapsed.as_nanos() as f64;
+        let [gfx, compute, dma, dec, enc, uvd_enc, vcn_jpeg] = {
             [
-                (pre_stat.gfx, self.gfx),
-                (pre_stat.compute, self.compute),
-                (pre_stat.dma, self.dma),
-                (pre_stat.dec, self.dec),
-                (pre_stat.enc, self.enc),
-                (pre_stat.uvd_enc, self.uvd_enc),
-                (pre_stat.vcn_jpeg, self.vcn_jpeg),
+                (pre_stat.gfx, self.gfx, self.engine_capacity.gfx),
+                (pre_stat.compute, self.compute, self.engine_capacity.compute),
+                (pre_stat.dma, self.dma, self.engine_capacity.dma),
+                (pre_stat.dec, self.dec, self.engine_capacity.dec),
+                (pre_stat.enc, self.enc, self.engine_capacity.enc),
+                (pre_stat.uvd_enc, self.uvd_enc, self.engine_capacity.uvd_enc),
+                (pre_stat.vcn_jpeg, self.vcn_jpeg, self.engine_capacity.vcn_jpeg),
             ]
-            .map(|(pre, cur)| {
-                let usage = if pre == 0 {
-                    0
-                } else {
-                    let tmp = cur.saturating_sub(pre);
+            .map(|(pre, cur, capacity)| {
+                if elapsed == 0.0 { return 0.0 }
 
-                    if tmp.is_negative() { 0 } else { tmp }
-                };
+                // the counter goes backwards when a DRM client is closed
+                let usage = cur.saturating_sub(pre).max(0) as f64 * 100.0 / elapsed;
 
-                (usage as u128 / (interval.as_nanos() / 100)) as i64
+                (usage / capacity.max(1) as f64).min(100.0)
             })
         };
 
-        Self {
-            vram_usage: self.vram_usage,
-            gtt_usage: self.gtt_usage,
-            cpu_accessible_usage: self.cpu_accessible_usage,
+        EngineUsage {
             gfx,
             compute,
             dma,
             dec,
             enc,
             uvd_enc,
-            vcn_jpeg: _vcn_jpeg,
+            vcn_jpeg,
         }
     }
 }
 
-pub fn get_self_pid() -> Option<i32> {
-    let link = std::fs::read_link("/proc/self").ok()?;
-    let path_str = link.to_str()?;
+/// Moves the selection by `step` rows, clamped to the table.
+/// The first row is selected if `selected` is not in `rows` (e.g. the process has exited).
+fn next_selection(rows: &[i32], selected: Option<i32>, step: isize) -> Option<i32> {
+    let last = rows.len().checked_sub(1)?;
+    let pos = match selected.and_then(|pid| rows.iter().position(|p| *p == pid)) {
+        Some(pos) => pos.saturating_add_signed(step).min(last),
+        None => 0,
+    };
+
+    Some(rows[pos])
+}
+
+/// Splits a line of fdinfo into the key, the value and the optional unit.
+/// e.g. "drm-memory-vram:\t1024 KiB" -> ("drm-memory-vram", 1024, Some("KiB"))
+/// Returns `None` for keys with a non-numeric value (e.g. "drm-driver", "drm-pdev").
+fn parse_key_value(s: &str) -> Option<(&str, u64, Option<&str>)> {
+    let (key, val) = s.split_once(':')?;
+    let mut val = val.split_whitespace();
+    let num = val.next()?.parse::<u64>().ok()?;
+
+    Some((key.trim(), num, val.next()))
+}
+
+/// The default unit of memory is byte.
+fn mem_to_kib(val: u64, unit: Option<&str>) -> Option<u64> {
+    match unit {
+        None => Some(val >> 10),
+        Some("KiB") => Some(val),
+        Some("MiB") => Some(val << 10),
+        Some("GiB") => Some(val << 20),
+        _ => None,
+    }
+}
+
+/// The default unit of frequency is Hz.
+fn freq_to_mhz(val: u64, unit: Option<&str>) -> Option<u64> {
+    match unit {
+        None | Some("Hz") => Some(val / 1_000_000),
+        Some("kHz" | "KHz") => Some(val / 1_000),
+        Some("MHz") => Some(val),
+        _ => None,
+    }
+}
+
+/// Returns the DRM client id and the stat of the fdinfo at `path`.
+/// `buf` is reused between calls.
+pub fn read_fdinfo(path: &Path, buf: &mut String) -> Option<(usize, FdInfoUsage)> {
+    let mut f = fs::File::open(path).ok()?;
+    buf.clear();
+    f.read_to_string(buf).ok()?;
+
+    let mut client_id: Option<usize> = None;
+    let mut stat = FdInfoUsage::default();
+
+    for l in buf.lines() {
+        let Some((key, val, unit)) = parse_key_value(l) else { continue };
+
+        if key == "drm-client-id" {
+            client_id = Some(val as usize);
+            continue;
+        }
+
+        stat.set_value(key, val, unit);
+    }
+
+    Some((client_id?, stat))
+}
+
+/// Replaces the previous stat of `key` with `stat` read at `now`,
+/// and returns the engine usage since the previous read.
+/// Engine usage is not available for the first read of `key`.
+fn diff_usage<K: Eq + std::hash::Hash>(
+    map: &mut HashMap<K, PreStat>,
+    key: K,
+    stat: &FdInfoUsage,
+    now: Instant,
+) -> EngineUsage {
+    if let Some((pre_stat, pre_time)) = map.get_mut(&key) {
+        let usage = stat.calc_usage(pre_stat, &now.duration_since(*pre_time));
+        *pre_stat = stat.clone();
+        *pre_time = now;
+
+        usage
+    } else {
+        map.insert(key, (stat.clone(), now));
+
+        EngineUsage::default()
+    }
+}
+
+fn write_usage(
+    buf: &mut String,
+    usage: &FdInfoUsage,
+    engine: &EngineUsage,
+) -> Result<(), fmt::Error> {
+    write!(buf, " {vram:>5} MiB|", vram = usage.vram_usage >> 10)?;
+
+    for (usage, label) in [
+        (engine.gfx, GFX_LABEL),
+        (engine.compute, COMPUTE_LABEL),
+        (engine.dma, DMA_LABEL),
+        (engine.dec, DEC_LABEL), // UVD/VCN
+        (engine.enc_total(), ENC_LABEL), // VCE/VCN, UVD
+        // (vcn_jpeg, JPEG_LABEL) // VCN
+    ] {
+        write!(buf, " {usage:>width$.1}%|", width = label.len().max(PERCENT_WIDTH))?;
+    }
+    writeln!(buf)?;
+
+    Ok(())
+}
+
+fn proc_json_map(pu: &ProcUsage) -> Map<String, Value> {
+    let mut m = usage_json_map(&pu.usage, &pu.engine);
+
+    let clients: Vec<Value> = pu.clients.iter().map(|cu| {
+        let mut c = usage_json_map(&cu.usage, &cu.engine);
+        c.insert("client_id".to_string(), cu.client_id.into());
+
+        c.into()
+    }).collect();
+
+    m.insert("clients".to_string(), clients.into());
+    m.insert("cgroup".to_string(), pu.cgroup.clone().into());
+    m.insert("user".to_string(), pu.user.clone().into());
+    m.insert("ns_pid".to_string(), pu.ns.inner_pid().into());
+    m.insert("ns_pgid".to_string(), pu.ns.inner_pgid().into());
+    m.insert("pid_ns".to_string(), pu.ns.pid_ns.into());
+    m.insert("mnt_ns".to_string(), pu.ns.mnt_ns.into());
+    m.insert(
+        "VRAM Leak".to_string(),
+        pu.leak_rate.map(|rate| json!({
+            "value": rate * 60.0 / 1024.0,
+            "unit": "MiB/min",
+        })).unwrap_or(Value::Null),
+    );
+    m.insert(
+        "KFD".to_string(),
+        pu.kfd.map(|kfd| json!({
+            "VRAM Usage": {
+                "value": kfd.vram_usage >> 10,
+                "unit": "MiB",
+            },
+            "SDMA": {
+                "value": pu.kfd_sdma,
+                "unit": "%",
+            },
+            "CU Occupancy": kfd.cu_occupancy,
+        })).unwrap_or(Value::Null),
+    );
+
+    m
+}
+
+fn usage_json_map(usage: &FdInfoUsage, engine: &EngineUsage) -> Map<String, Value> {
+    let mut m = Map::new();
+
+    for (val, label) in [
+        (usage.vram_usage >> 10, "VRAM Usage"),
+        (usage.gtt_usage >> 10, "GTT Usage"),
+    ] {
+        m.insert(
+            label.to_string(),
+            json!({
+                "value": val,
+                "unit": "MiB",
+            }),
+        );
+    }
+
+    for (val, label) in [
+        (engine.gfx, GFX_LABEL),
+        (engine.compute, COMPUTE_LABEL),
+        (engine.dma, DMA_LABEL),
+        (engine.dec, DEC_LABEL), // UVD/VCN
+        (engine.enc_total(), ENC_LABEL), // VCE/VCN, UVD
+        // (vcn_jpeg, JPEG_LABEL) // VCN
+    ] {
+        m.insert(
+            label.to_string(),
+            json!({
+                "value": val,
+                "unit": "%",
+            }),
+        );
+    }
+
+    m
+}
+
+/// Returns the DRM nodes (e.g. "/dev/dri/card0", "/dev/dri/renderD128") that belong to
+/// the same GPU as `device_path`.
+/// Compositors, Xorg and KMS clients open the primary node instead of the render node.
+pub fn get_drm_nodes(device_path: &str) -> Vec<String> {
+    let mut nodes = vec![device_path.to_string()];
+
+    let Some(node_name) = Path::new(device_path).file_name()
+        .and_then(|name| name.to_str()) else { return nodes };
+    let Ok(drm_dir) = fs::read_dir(format!("/sys/class/drm/{node_name}/device/drm")) else {
+        return nodes
+    };
+
+    for dir_entry in drm_dir.flatten() {
+        let Some(name) = dir_entry.file_name().to_str()
+            .map(|name| format!("/dev/dri/{name}")) else { continue };
 
-    path_str.parse::<i32>().ok()
+        if !nodes.contains(&name) {
+            nodes.push(name);
+        }
+    }
+
+    nodes
 }
 
-fn get_fds(pid: i32, target_device: &str) -> Vec<i32> {
+pub fn get_fds(pid: i32, proc_root: &Path, device_nodes: &[String]) -> Vec<i32> {
     let mut fds: Vec<i32> = Vec::new();
 
-    let fd_path = format!("/proc/{pid}/fd/");
+    let fd_path = proc_root.join(format!("{pid}/fd/"));
 
     let Ok(fd_list) = fs::read_dir(&fd_path) else { return fds };
 
@@ -411,8 +1178,8 @@ fn get_fds(pid: i32, target_device: &str) -> Vec<i32> {
         let Ok(dir_entry) = fd_link.and_then(|fd_link| Ok(fd_link.path())) else { continue };
         let Ok(link) = fs::read_link(&dir_entry) else { continue };
 
-        // e.g. "/dev/dri/renderD128"
-        if link.starts_with(target_device) {
+        // e.g. "/dev/dri/renderD128", "/dev/dri/card0"
+        if device_nodes.iter().any(|node| link.starts_with(node)) {
             let Some(fd_num) = dir_entry.file_name()
                 .and_then(|name| name.to_str())
                 .and_then(|name| name.parse::<i32>().ok()) else { continue };
@@ -423,9 +1190,51 @@ fn get_fds(pid: i32, target_device: &str) -> Vec<i32> {
     fds
 }
 
-fn get_all_processes() -> Vec<i32> {
+/// Returns the cgroup path of `pid`.
+/// The unified hierarchy (cgroup v2) is preferred, then the systemd hierarchy (cgroup v1).
+/// e.g. "0::/user.slice/user-1000.slice/session-2.scope"
+fn get_cgroup(pid: i32, proc_root: &Path) -> String {
+    let Ok(s) = fs::read_to_string(proc_root.join(format!("{pid}/cgroup"))) else {
+        return String::new()
+    };
+    let mut cgroup: Option<&str> = None;
+
+    for l in s.lines() {
+        let mut split = l.splitn(3, ':');
+        let [Some(id), Some(controllers), Some(path)] = [split.next(), split.next(), split.next()] else {
+            continue
+        };
+
+        if id == "0" && controllers.is_empty() {
+            return path.to_string();
+        }
+        if controllers == "name=systemd" || cgroup.is_none() {
+            cgroup = Some(path);
+        }
+    }
+
+    cgroup.unwrap_or("").to_string()
+}
+
+/// e.g. ("/user.slice/app.slice", 9) -> "app.slice"
+fn truncate_head(s: &str, len: usize) -> String {
+    let skip = s.chars().count().saturating_sub(len);
+
+    s.chars().skip(skip).collect()
+}
+
+/// Returns the real UID of `pid`
+fn get_uid(pid: i32, proc_root: &Path) -> Option<u32> {
+    let s = fs::read_to_string(proc_root.join(format!("{pid}/status"))).ok()?;
+    // "Uid:\t<real>\t<effective>\t<saved set>\t<filesystem>"
+    let uid = s.lines().find_map(|l| l.strip_prefix("Uid:"))?;
+
+    uid.split_whitespace().next()?.parse().ok()
+}
+
+pub fn get_all_processes(proc_root: &Path) -> Vec<i32> {
     let mut pids: Vec<i32> = Vec::new();
-    let Ok(proc_dir) = fs::read_dir("/proc") else { return pids };
+    let Ok(proc_dir) = fs::read_dir(proc_root) else { return pids };
 
     for dir_entry in proc_dir.into_iter().flatten() {
         let Ok(metadata) = dir_entry.metadata() else { continue };
@@ -439,26 +1248,380 @@ fn get_all_processes() -> Vec<i32> {
     pids
 }
 
-pub fn update_index(vec_info: &mut Vec<ProcInfo>, target_device: &str, self_pid: i32) {
-    vec_info.clear();
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stat::fake_proc::FakeProc;
 
-    for p in &get_all_processes() {
-        let pid = *p;
-        if pid == self_pid { continue }
+    const CARD: &str = "/dev/dri/card0";
+    const RENDER: &str = "/dev/dri/renderD128";
 
-        let fds = get_fds(pid, target_device);
+    fn fdinfo(client_id: usize, vram_kib: u64) -> String {
+        format!("drm-driver:\tamdgpu\ndrm-client-id:\t{client_id}\ndrm-memory-vram:\t{vram_kib} KiB\n")
+    }
 
-        if !fds.is_empty() {
-            let Ok(mut name) = fs::read_to_string(format!("/proc/{pid}/comm")) else {
-                continue
-            };
-            name.pop(); // trim '\n'
+    #[test]
+    fn card_and_render_fds_are_attributed() {
+        let proc = FakeProc::new();
+        let nodes = [RENDER.to_string(), CARD.to_string()];
+
+        proc.add_proc(100, "Xorg");
+        proc.add_fd(100, 3, CARD, &fdinfo(1, 1024));
+        proc.add_fd(100, 4, RENDER, &fdinfo(2, 2048));
+        proc.add_fd(100, 5, "/dev/null", "pos:\t0\n");
+
+        let mut fds = get_fds(100, proc.path(), &nodes);
+        fds.sort();
+        assert_eq!(fds, [3, 4]);
+
+        let mut view = FdInfoView::new(proc.path());
+        view.get_proc_usage(&ProcInfo::from_pid(100, proc.path(), &nodes));
+
+        let pu = &view.proc_usage[0];
+        assert_eq!(pu.usage.vram_usage, 3072);
+        assert_eq!(pu.clients.iter().map(|cu| cu.client_id).collect::<Vec<_>>(), [1, 2]);
+    }
+
+    #[test]
+    fn process_is_alive() {
+        let proc = FakeProc::new();
+        proc.add_proc(100, "app");
+        let proc_info = ProcInfo { pid: 100, name: "app".to_string(), ..Default::default() };
+
+        assert!(proc_info.is_alive(proc.path()));
+
+        // the PID is reused by another process
+        fs::write(proc.path().join("100/comm"), "other\n").unwrap();
+        assert!(!proc_info.is_alive(proc.path()));
+
+        proc.remove_proc(100);
+        assert!(!proc_info.is_alive(proc.path()));
+    }
 
-            vec_info.push(ProcInfo {
+    #[test]
+    fn render_node_only() {
+        let proc = FakeProc::new();
+
+        proc.add_proc(100, "Xorg");
+        proc.add_fd(100, 3, CARD, &fdinfo(1, 1024));
+        proc.add_fd(100, 4, RENDER, &fdinfo(2, 2048));
+
+        assert_eq!(get_fds(100, proc.path(), &[RENDER.to_string()]), [4]);
+    }
+
+    const AMDGPU_FDINFO: &str = "\
+pos:\t0
+flags:\t02100002
+mnt_id:\t26
+ino:\t1074
+drm-driver:\tamdgpu
+drm-pdev:\t0000:03:00.0
+drm-client-id:\t42
+drm-memory-vram:\t8192 KiB
+drm-memory-gtt:\t2048 KiB
+drm-memory-cpu:\t0 KiB
+drm-engine-gfx:\t1000000 ns
+drm-engine-compute:\t2000000 ns
+drm-engine-dma:\t300 ns
+drm-engine-dec:\t0 ns
+drm-engine-enc:\t0 ns
+";
+
+    #[test]
+    fn read_fdinfo_from_proc_root() {
+        let proc = FakeProc::new();
+        proc.add_proc(200, "glxgears");
+        proc.add_fd(200, 7, RENDER, AMDGPU_FDINFO);
+
+        let mut buf = String::new();
+        let (id, stat) = read_fdinfo(&proc.path().join("200/fdinfo/7"), &mut buf).unwrap();
+
+        assert_eq!(id, 42);
+        assert_eq!(stat.vram_usage, 8192);
+        assert_eq!(stat.gtt_usage, 2048);
+        assert_eq!(stat.cpu_accessible_usage, 0);
+        assert_eq!(stat.gfx, 1_000_000);
+        assert_eq!(stat.compute, 2_000_000);
+        assert_eq!(stat.dma, 300);
+
+        // fdinfo of other drivers or files without "drm-client-id"
+        proc.add_fd(200, 8, RENDER, "pos:\t0\nflags:\t02\n");
+        assert!(read_fdinfo(&proc.path().join("200/fdinfo/8"), &mut buf).is_none());
+        assert!(read_fdinfo(&proc.path().join("200/fdinfo/9"), &mut buf).is_none());
+    }
+
+    #[test]
+    fn proc_usage_from_proc_root() {
+        let proc = FakeProc::new();
+        let nodes = [RENDER.to_string()];
+        proc.add_proc(200, "glxgears");
+        proc.add_fd(200, 7, RENDER, AMDGPU_FDINFO);
+        // dup'ed fd of the same DRM client
+        proc.add_fd(200, 8, RENDER, AMDGPU_FDINFO);
+
+        let proc_info = ProcInfo::from_pid(200, proc.path(), &nodes);
+        assert_eq!(proc_info.name, "glxgears");
+
+        let mut view = FdInfoView::new(proc.path());
+        view.get_proc_usage(&proc_info);
+
+        let pu = &view.proc_usage[0];
+        assert_eq!(pu.usage.vram_usage, 8192);
+        assert_eq!(pu.usage.gtt_usage, 2048);
+        assert_eq!(pu.clients.len(), 1);
+        // the first read has no previous stat
+        assert_eq!(pu.engine, EngineUsage::default());
+
+        let busy = AMDGPU_FDINFO.replace("1000000 ns", "900000000 ns");
+        proc.write_fdinfo(200, 7, &busy);
+        proc.write_fdinfo(200, 8, &busy);
+        view.clear_usage();
+        view.get_proc_usage(&proc_info);
+
+        assert!(0.0 < view.proc_usage[0].engine.gfx);
+        assert!(view.proc_usage[0].engine.gfx <= 100.0);
+        assert_eq!(view.proc_usage[0].engine.compute, 0.0);
+    }
+
+    #[test]
+    fn user_name_cache() {
+        let mut view = FdInfoView::default();
+        // not in "/etc/passwd"
+        let uid = u32::MAX - 1;
+
+        assert_eq!(view.get_user_name(None), "");
+        assert_eq!(view.get_user_name(Some(uid)), uid.to_string());
+        assert_eq!(view.user_names.get(&uid), Some(&uid.to_string()));
+        assert_eq!(view.get_user_name(Some(uid)), uid.to_string());
+    }
+
+    #[test]
+    fn calc_usage_percent() {
+        let pre = FdInfoUsage { gfx: 1_000_000, compute: 0, dma: 500, ..Default::default() };
+        let cur = FdInfoUsage { gfx: 6_000_000, compute: 20_000_000, dma: 500, ..Default::default() };
+        let usage = cur.calc_usage(&pre, &Duration::from_millis(10));
+
+        assert_eq!(usage.gfx, 50.0);
+        assert_eq!(usage.compute, 100.0); // clamped
+        assert_eq!(usage.dma, 0.0);
+
+        // the counter goes backwards when a DRM client is closed
+        let usage = pre.calc_usage(&cur, &Duration::from_millis(10));
+        assert_eq!(usage.gfx, 0.0);
+
+        let usage = cur.calc_usage(&pre, &Duration::ZERO);
+        assert_eq!(usage, EngineUsage::default());
+    }
+
+    #[test]
+    fn calc_usage_capacity() {
+        let capacity = EngineValues { compute: 4, ..Default::default() };
+        let pre = FdInfoUsage { engine_capacity: capacity, ..Default::default() };
+        let cur = FdInfoUsage {
+            gfx: 20_000_000,
+            compute: 20_000_000,
+            engine_capacity: capacity,
+            ..Default::default()
+        };
+        let usage = cur.calc_usage(&pre, &Duration::from_millis(10));
+
+        assert_eq!(usage.gfx, 100.0);
+        // 2 of 4 rings are busy
+        assert_eq!(usage.compute, 50.0);
+
+        let cur = FdInfoUsage { compute: 80_000_000, ..cur };
+        assert_eq!(cur.calc_usage(&pre, &Duration::from_millis(10)).compute, 100.0);
+    }
+
+    #[test]
+    fn calc_usage_does_not_overflow() {
+        let pre = FdInfoUsage { gfx: i64::MIN, dma: i64::MAX, ..Default::default() };
+        let cur = FdInfoUsage { gfx: i64::MAX, dma: i64::MIN, ..Default::default() };
+        let usage = cur.calc_usage(&pre, &Duration::from_millis(10));
+
+        assert_eq!(usage.gfx, 100.0);
+        assert_eq!(usage.dma, 0.0);
+    }
+
+    #[test]
+    fn group_usage_is_clamped() {
+        let mut view = FdInfoView::default();
+
+        for (pid, gfx) in [(1, 70.0), (2, 60.0), (3, 10.0)] {
+            view.proc_usage.push(ProcUsage {
                 pid,
-                name,
-                fds,
+                user: if pid == 3 { "root" } else { "user" }.to_string(),
+                engine: EngineUsage { gfx, dma: gfx / 10.0, ..Default::default() },
+                ..Default::default()
             });
+            view.total_engine += view.proc_usage.last().unwrap().engine;
         }
+
+        let groups = view.group_usage(&FdInfoGroupType::User);
+        assert_eq!(groups[0].name, "user");
+        assert_eq!(groups[0].pids, [1, 2]);
+        assert_eq!(groups[0].engine.gfx, 100.0);
+        assert_eq!(groups[0].engine.dma, 13.0);
+        assert_eq!(groups[1].engine.gfx, 10.0);
+
+        assert_eq!(view.engines().gfx, 100.0);
+    }
+
+    #[test]
+    fn parse_key_value_units() {
+        assert_eq!(parse_key_value("drm-memory-vram:\t1024 KiB"), Some(("drm-memory-vram", 1024, Some("KiB"))));
+        assert_eq!(parse_key_value("drm-engine-gfx:\t12345 ns"), Some(("drm-engine-gfx", 12345, Some("ns"))));
+        assert_eq!(parse_key_value("drm-client-id:\t7"), Some(("drm-client-id", 7, None)));
+        assert_eq!(parse_key_value("  drm-total-gtt :   3   MiB  "), Some(("drm-total-gtt", 3, Some("MiB"))));
+    }
+
+    #[test]
+    fn parse_key_value_malformed() {
+        for l in [
+            "",
+            "drm-driver:\tamdgpu",
+            "drm-pdev:\t0000:03:00.0",
+            "drm-memory-vram",
+            "drm-memory-vram:",
+            "drm-memory-vram:\t-1 KiB",
+            "drm-memory-vram:\t18446744073709551616 KiB",
+            ":",
+        ] {
+            assert_eq!(parse_key_value(l), None, "{l:?}");
+        }
+    }
+
+    #[test]
+    fn selection() {
+        let rows = [30, 10, 20];
+
+        assert_eq!(next_selection(&rows, None, 1), Some(30));
+        assert_eq!(next_selection(&rows, Some(30), 1), Some(10));
+        assert_eq!(next_selection(&rows, Some(20), 1), Some(20));
+        assert_eq!(next_selection(&rows, Some(10), -1), Some(30));
+        assert_eq!(next_selection(&rows, Some(30), -1), Some(30));
+        // the selected process has exited
+        assert_eq!(next_selection(&rows, Some(40), -1), Some(30));
+        assert_eq!(next_selection(&[], Some(30), 1), None);
+    }
+
+    #[test]
+    fn selected_row_is_marked() {
+        let mut view = FdInfoView::default();
+        for (pid, user) in [(1, "alice"), (2, "bob")] {
+            view.proc_usage.push(ProcUsage {
+                pid,
+                name: format!("proc{pid}"),
+                user: user.to_string(),
+                ..Default::default()
+            });
+        }
+        view.selected = Some(2);
+
+        view.print_usage(false).unwrap();
+        let lines: Vec<&str> = view.text.buf.lines().collect();
+
+        assert!(lines[0].starts_with(" proc1"));
+        assert!(lines[1].starts_with(">proc2"));
+    }
+
+    #[test]
+    fn engine_time_out_of_range() {
+        let mut stat = FdInfoUsage { gfx: 5, ..Default::default() };
+
+        stat.set_value("drm-engine-gfx", u64::MAX, Some("ns"));
+        assert_eq!(stat.gfx, 5);
+        stat.set_value("drm-engine-gfx", i64::MAX as u64, Some("ns"));
+        assert_eq!(stat.gfx, i64::MAX);
+    }
+
+    #[test]
+    fn mem_units() {
+        assert_eq!(mem_to_kib(4096, None), Some(4));
+        assert_eq!(mem_to_kib(4, Some("KiB")), Some(4));
+        assert_eq!(mem_to_kib(4, Some("MiB")), Some(4 << 10));
+        assert_eq!(mem_to_kib(4, Some("GiB")), Some(4 << 20));
+        assert_eq!(mem_to_kib(4, Some("kB")), None);
+    }
+
+    #[test]
+    fn freq_units() {
+        assert_eq!(freq_to_mhz(2_500_000_000, None), Some(2500));
+        assert_eq!(freq_to_mhz(2_500_000_000, Some("Hz")), Some(2500));
+        assert_eq!(freq_to_mhz(2_500_000, Some("kHz")), Some(2500));
+        assert_eq!(freq_to_mhz(2_500_000, Some("KHz")), Some(2500));
+        assert_eq!(freq_to_mhz(2500, Some("MHz")), Some(2500));
+        assert_eq!(freq_to_mhz(2, Some("GHz")), None);
+    }
+
+    fn parse(fdinfo: &str) -> FdInfoUsage {
+        let mut stat = FdInfoUsage::default();
+
+        for l in fdinfo.lines() {
+            let Some((key, val, unit)) = parse_key_value(l) else { continue };
+            stat.set_value(key, val, unit);
+        }
+
+        stat
+    }
+
+    #[test]
+    fn drm_usage_stats_keys() {
+        let stat = parse("\
+drm-resident-vram:\t2 MiB
+drm-resident-gtt:\t1 GiB
+drm-resident-cpu:\t4096
+drm-total-vram:\t3 MiB
+drm-total-gtt:   \t  8 KiB
+drm-shared-vram:\t1 MiB
+drm-active-gtt:\t4 KiB
+drm-purgeable-vram:\t16 KiB
+drm-purgeable-cpu:\t1 MiB
+drm-engine-capacity-gfx:\t1
+drm-engine-capacity-compute:\t4
+drm-engine-enc_1:\t10 ns
+drm-engine-jpeg:\t20 ns
+drm-cycles-gfx:\t123456
+drm-cycles-dec:\t789
+drm-maxfreq-gfx:\t2500 MHz
+drm-maxfreq-dec:\t1200000 kHz
+drm-maxfreq-enc:\t800000000
+");
+
+        assert_eq!(stat.vram_usage, 2048);
+        assert_eq!(stat.gtt_usage, 1 << 20);
+        assert_eq!(stat.cpu_accessible_usage, 4);
+        assert_eq!(stat.total, MemRegions { vram: 3072, gtt: 8, cpu: 0 });
+        assert_eq!(stat.shared, MemRegions { vram: 1024, ..Default::default() });
+        assert_eq!(stat.active, MemRegions { gtt: 4, ..Default::default() });
+        assert_eq!(stat.purgeable, MemRegions { vram: 16, gtt: 0, cpu: 1024 });
+        assert_eq!(stat.engine_capacity, EngineValues { gfx: 1, compute: 4, ..Default::default() });
+        // "engine-capacity-*" is not an engine time
+        assert_eq!(stat.gfx, 0);
+        assert_eq!(stat.compute, 0);
+        assert_eq!(stat.uvd_enc, 10);
+        assert_eq!(stat.vcn_jpeg, 20);
+        assert_eq!(stat.cycles, EngineValues { gfx: 123456, dec: 789, ..Default::default() });
+        assert_eq!(stat.maxfreq, EngineValues { gfx: 2500, dec: 1200, enc: 800, ..Default::default() });
+    }
+
+    #[test]
+    fn unknown_keys_are_ignored() {
+        let stat = parse("\
+drm-memory-vram:\t1 KiB
+drm-memory-vram2:\t1 KiB
+drm-memory-gds:\t1 KiB
+drm-total-vram:\t1 TiB
+drm-engine-vpe:\t100 ns
+drm-engine-capacity-vpe:\t2
+drm-cycles-vpe:\t1
+drm-maxfreq-gfx:\t1 GHz
+drm-unknown:\t1
+amd-memory-visible-vram:\t1 KiB
+drm-engine-gfx
+drm-engine-gfx: ns
+");
+
+        assert_eq!(stat, FdInfoUsage { vram_usage: 1, ..Default::default() });
     }
 }
@@ -0,0 +1,257 @@
+use super::{Opt, TopView, UsageBars, BITS, toggle_view};
+use libdrm_amdgpu_sys::AMDGPU::{DeviceHandle, GRBM_OFFSET};
+use serde_json::{Map, Value};
+use std::time::Duration;
+
+// ref: drivers/gpu/drm/amd/include/asic_reg/gc/gc_9_0_offset.h
+
+/// offsets of mmGRBM_STATUS_SE0..SE3 from mmGRBM_STATUS.
+/// SE4 and later have no register of their own, and are not read.
+const SE_OFFSETS: [u32; 4] = [0x1, 0x2, 0xA, 0xB];
+
+/// Busy percentage of each shader engine, from GRBM_STATUS_SE.
+/// An SE is busy if any bit of the "GRBM_STATUS_SE" bit table is set.
+pub struct GrbmSeView {
+    /// number of shader engines of the device, SE4 and later are not read
+    max_se: usize,
+    /// busy bits, from the bit tables
+    pub index: Vec<(String, usize)>,
+    /// SEs allowed to be read
+    readable: Vec<bool>,
+    /// per SE
+    bits: Vec<BITS>,
+    busy: Vec<u32>,
+    bars: UsageBars,
+}
+
+impl GrbmSeView {
+    pub const TITLE: &str = "GRBM_STATUS_SE";
+
+    /// `max_se`: number of shader engines of the device
+    pub fn new(max_se: u32, index: Vec<(String, usize)>, stats_window: Duration) -> Self {
+        let max_se = max_se as usize;
+        let num_se = max_se.min(SE_OFFSETS.len());
+        let names = (0..num_se).map(|se| format!("SE{se}")).collect();
+
+        Self {
+            max_se,
+            index,
+            readable: vec![true; num_se],
+            bits: (0..num_se).map(|_| BITS::default()).collect(),
+            busy: vec![0; num_se],
+            bars: UsageBars::new(names, stats_window),
+        }
+    }
+
+    fn offset(se: usize) -> u32 {
+        GRBM_OFFSET + SE_OFFSETS[se]
+    }
+
+    /// `false` if no SE register is allowed to be read by the driver, or no bits are defined
+    pub fn check_reg_offset(&mut self, amdgpu_dev: &DeviceHandle) -> bool {
+        if SE_OFFSETS.len() < self.max_se {
+            println!("GRBM_STATUS_SE: SE{}..SE{} have no register, not shown.", SE_OFFSETS.len(), self.max_se - 1);
+        }
+
+        self.check(amdgpu_dev, true)
+    }
+
+    /// `check_reg_offset` without the messages, for the JSON output
+    pub fn is_readable(&mut self, amdgpu_dev: &DeviceHandle) -> bool {
+        self.check(amdgpu_dev, false)
+    }
+
+    fn check(&mut self, amdgpu_dev: &DeviceHandle, verbose: bool) -> bool {
+        if self.index.is_empty() { return false }
+
+        for (se, readable) in self.readable.iter_mut().enumerate() {
+            let offset = Self::offset(se);
+
+            if let Err(err) = amdgpu_dev.read_mm_registers(offset) {
+                if verbose {
+                    println!("mmGRBM_STATUS_SE{se} ({offset:#X}) register is not allowed. ({err})");
+                }
+                *readable = false;
+            }
+        }
+
+        self.readable.iter().any(|r| *r)
+    }
+
+    fn usage(&self) -> Vec<u32> {
+        self.busy.iter().zip(self.bits.iter()).map(|(busy, bits)| {
+            if bits.count == 0 { return 0 }
+
+            (*busy as u64 * 100 / bits.count as u64) as u32
+        }).collect()
+    }
+
+    pub fn top_view(
+        &self,
+        visible: bool,
+    ) -> TopView {
+        self.bars.top_view(Self::TITLE, visible)
+    }
+
+    pub fn read_reg(&mut self, amdgpu_dev: &DeviceHandle) {
+        let mask = self.mask();
+
+        for se in 0..self.readable.len() {
+            if !self.readable[se] { continue }
+            let Ok(out) = amdgpu_dev.read_mm_registers(Self::offset(se)) else { continue };
+
+            self.acc(se, out, mask);
+        }
+    }
+
+    /// bits of the table, any of them set means the SE is busy
+    fn mask(&self) -> u32 {
+        self.index.iter().fold(0, |mask, (_, pos)| mask | (1 << pos))
+    }
+
+    fn acc(&mut self, se: usize, out: u32, mask: u32) {
+        self.bits[se].acc(out);
+        if is_busy(out, mask) {
+            self.busy[se] = self.busy[se].saturating_add(1);
+        }
+    }
+
+    pub fn set_value(&self) {
+        self.bars.set_value(&self.usage());
+    }
+
+    /// Adds the usage of the current refresh period to the stats window
+    pub fn update_stats(&mut self) {
+        let usage = self.usage();
+        self.bars.update_stats(&usage);
+    }
+
+    pub fn clear(&mut self) {
+        self.bits.iter_mut().for_each(|bits| bits.clear());
+        self.busy.fill(0);
+    }
+
+    pub fn dump(&mut self) {
+        self.update_stats();
+        self.set_value();
+        self.clear();
+    }
+
+    /// `null` for SEs not allowed to be read, and SE4 and later
+    pub fn json_value(&self) -> Value {
+        let mut m = Map::new();
+
+        for (se, usage) in self.usage().into_iter().enumerate() {
+            let v = if self.readable[se] {
+                let mut v = self.bars.json_value(se, usage);
+                let bits: Map<String, Value> = self.index.iter()
+                    .map(|(name, pos)| (name.clone(), self.bits[se].percent(*pos).into()))
+                    .collect();
+                v["bits"] = bits.into();
+
+                v
+            } else {
+                Value::Null
+            };
+
+            m.insert(format!("SE{se}"), v);
+        }
+
+        for se in self.readable.len()..self.max_se {
+            m.insert(format!("SE{se}"), Value::Null);
+        }
+
+        m.into()
+    }
+
+    pub fn cb(siv: &mut cursive::Cursive) {
+        {
+            let mut opt = siv.user_data::<Opt>().unwrap().lock().unwrap();
+            opt.grbm_se ^= true;
+        }
+
+        siv.call_on_name(Self::TITLE, toggle_view);
+    }
+}
+
+fn is_busy(reg: u32, mask: u32) -> bool {
+    (reg & mask) != 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn view(max_se: u32) -> GrbmSeView {
+        let index = vec![("Texture Pipe".to_string(), 25), ("Color Block".to_string(), 31)];
+
+        GrbmSeView::new(max_se, index, Duration::from_secs(60))
+    }
+
+    #[test]
+    fn offsets() {
+        assert_eq!(GrbmSeView::offset(0), GRBM_OFFSET + 0x1);
+        assert_eq!(GrbmSeView::offset(3), GRBM_OFFSET + 0xB);
+    }
+
+    #[test]
+    fn se4_and_later_are_null() {
+        let view = view(8);
+
+        assert_eq!(view.bars.names.len(), 4);
+        assert_eq!(view.bars.names[3], "SE3");
+
+        let v = view.json_value();
+        assert_eq!(v.as_object().unwrap().len(), 8);
+        assert!(v["SE3"].is_object());
+        assert!(v["SE4"].is_null());
+        assert!(v["SE7"].is_null());
+    }
+
+    #[test]
+    fn busy_bits() {
+        let mask = view(1).mask();
+        assert_eq!(mask, (1 << 25) | (1 << 31));
+
+        // bits not in the table do not count as busy
+        assert!(!is_busy(0, mask));
+        assert!(!is_busy(1 << 23, mask));
+        assert!(is_busy(1 << 25, mask));
+        assert!(is_busy((1 << 23) | (1 << 31), mask));
+    }
+
+    #[test]
+    fn busy_from_table_bits() {
+        let mut view = view(2);
+        let mask = view.mask();
+
+        view.acc(0, 1 << 23, mask);
+        view.acc(0, 1 << 25, mask);
+        view.acc(0, (1 << 25) | (1 << 31), mask);
+        view.acc(0, 0, mask);
+        view.acc(1, 1 << 31, mask);
+
+        assert_eq!(view.usage(), [50, 100]);
+
+        let v = view.json_value();
+        assert_eq!(v["SE0"]["usage"], 50);
+        assert_eq!(v["SE0"]["bits"]["Texture Pipe"], 50);
+        assert_eq!(v["SE0"]["bits"]["Color Block"], 25);
+
+        view.update_stats();
+        assert_eq!(view.bars.stats[1].stats().unwrap().max, 100);
+
+        view.clear();
+        assert_eq!(view.usage(), [0, 0]);
+    }
+
+    #[test]
+    fn unreadable_se_is_null() {
+        let mut view = view(2);
+        view.readable[1] = false;
+
+        let v = view.json_value();
+        assert!(v["SE0"].is_object());
+        assert!(v["SE1"].is_null());
+    }
+}
@@ -0,0 +1,82 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// ref: drivers/gpu/drm/amd/amdkfd/kfd_process.c
+// ref: drivers/gpu/drm/amd/amdkfd/kfd_topology.c
+
+const KFD_ROOT: &str = "/sys/class/kfd/kfd";
+
+/// Usage of a KFD (ROCm/HIP) process on the device
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, PartialOrd)]
+pub struct KfdUsage {
+    pub vram_usage: u64, // KiB
+    pub sdma: u64, // us, cumulative
+    /// number of CUs occupied by waves of the process
+    pub cu_occupancy: Option<u64>,
+}
+
+/// Reads "/sys/class/kfd/kfd/proc/<pid>/" for the `gpu_id` of the device
+#[derive(Clone, Debug)]
+pub struct KfdCollector {
+    pub gpu_id: u32,
+    proc_dir: PathBuf,
+}
+
+impl KfdCollector {
+    /// `None` if KFD is not available for the device.
+    /// `gpu_id` is found by `drm_render_minor` of the KFD topology.
+    pub fn new(device_path: &str) -> Option<Self> {
+        let minor: u32 = Path::new(device_path).file_name()?
+            .to_str()?
+            .strip_prefix("renderD")?
+            .parse().ok()?;
+        let nodes = fs::read_dir(Path::new(KFD_ROOT).join("topology/nodes")).ok()?;
+
+        for node in nodes.flatten() {
+            let path = node.path();
+            let Ok(props) = fs::read_to_string(path.join("properties")) else { continue };
+            let render_minor = props.lines()
+                .find_map(|l| l.strip_prefix("drm_render_minor "))
+                .and_then(|v| v.trim().parse::<u32>().ok());
+
+            if render_minor != Some(minor) { continue }
+
+            let gpu_id = fs::read_to_string(path.join("gpu_id")).ok()?.trim().parse().ok()?;
+            // CPU nodes have gpu_id 0
+            if gpu_id == 0 { continue }
+
+            return Some(Self {
+                gpu_id,
+                proc_dir: Path::new(KFD_ROOT).join("proc"),
+            });
+        }
+
+        None
+    }
+
+    /// PIDs of the KFD processes using the device
+    pub fn pids(&self) -> Vec<i32> {
+        let Ok(proc_dir) = fs::read_dir(&self.proc_dir) else { return Vec::new() };
+        let vram = format!("vram_{}", self.gpu_id);
+
+        proc_dir.flatten().filter_map(|dir_entry| {
+            let pid = dir_entry.file_name().to_str()?.parse::<i32>().ok()?;
+
+            dir_entry.path().join(&vram).exists().then_some(pid)
+        }).collect()
+    }
+
+    pub fn read(&self, pid: i32) -> Option<KfdUsage> {
+        let path = self.proc_dir.join(pid.to_string());
+        let gpu_id = self.gpu_id;
+        let read_u64 = |name: String| -> Option<u64> {
+            fs::read_to_string(path.join(name)).ok()?.trim().parse().ok()
+        };
+
+        Some(KfdUsage {
+            vram_usage: read_u64(format!("vram_{gpu_id}"))? >> 10,
+            sdma: read_u64(format!("sdma_{gpu_id}")).unwrap_or(0),
+            cu_occupancy: read_u64(format!("stats_{gpu_id}/cu_occupancy")),
+        })
+    }
+}
@@ -2,8 +2,8 @@ use super::{DeviceHandle, toggle_view, Opt};
 use libdrm_amdgpu_sys::AMDGPU::{
     GRBM_OFFSET,
     GRBM2_OFFSET,
-    // SRBM_OFFSET,
-    // SRBM2_OFFSET,
+    SRBM_OFFSET,
+    SRBM2_OFFSET,
     CP_STAT_OFFSET
 };
 
@@ -12,8 +12,8 @@ use libdrm_amdgpu_sys::AMDGPU::{
 pub enum PCType {
     GRBM,
     GRBM2,
-    // SRBM,
-    // SRBM2,
+    SRBM,
+    SRBM2,
     CP_STAT,
 }
 
@@ -25,25 +25,35 @@ impl fmt::Display for PCType {
 }
 
 impl PCType {
+    pub const ALL: [Self; 5] = [Self::GRBM, Self::GRBM2, Self::SRBM, Self::SRBM2, Self::CP_STAT];
+
     pub const fn offset(&self) -> u32 {
         match self {
             Self::GRBM => GRBM_OFFSET,
             Self::GRBM2 => GRBM2_OFFSET,
-            // Self::SRBM => SRBM_OFFSET,
-            // Self::SRBM2 => SRBM2_OFFSET,
+            Self::SRBM => SRBM_OFFSET,
+            Self::SRBM2 => SRBM2_OFFSET,
             Self::CP_STAT => CP_STAT_OFFSET,
         }
     }
 
-    pub fn check_reg_offset(&self, amdgpu_dev: &DeviceHandle) -> bool {
-        let offset = self.offset();
-        let reg_name = match self {
+    pub fn is_readable(&self, amdgpu_dev: &DeviceHandle) -> bool {
+        amdgpu_dev.read_mm_registers(self.offset()).is_ok()
+    }
+
+    pub const fn reg_name(&self) -> &'static str {
+        match self {
             Self::GRBM => "mmGRBM_STATUS",
             Self::GRBM2 => "mmGRBM2_STATUS2",
-            // Self::SRBM => "mmSRBM_STATUS",
-            // Self::SRBM2 => "mmSRBM2_STATUS2",
+            Self::SRBM => "mmSRBM_STATUS",
+            Self::SRBM2 => "mmSRBM_STATUS2",
             Self::CP_STAT => "mmCP_STAT_STATUS",
-        };
+        }
+    }
+
+    pub fn check_reg_offset(&self, amdgpu_dev: &DeviceHandle) -> bool {
+        let offset = self.offset();
+        let reg_name = self.reg_name();
 
         amdgpu_dev.read_mm_registers(offset).map_or_else(|err| {
             println!("{reg_name} ({offset:#X}) register is not allowed. ({err})");
@@ -62,16 +72,14 @@ impl PCType {
                 let mut opt = opt.lock().unwrap();
                 opt.grbm2 ^= true;
             },
-            /*
             Self::SRBM => |opt: &mut Opt| {
                 let mut opt = opt.lock().unwrap();
-                opt.uvd ^= true;
+                opt.srbm ^= true;
             },
             Self::SRBM2 => |opt: &mut Opt| {
                 let mut opt = opt.lock().unwrap();
-                opt.srbm ^= true;
+                opt.srbm2 ^= true;
             },
-            */
             Self::CP_STAT => |opt: &mut Opt| {
                 let mut opt = opt.lock().unwrap();
                 opt.cp_stat ^= true;
@@ -4,6 +4,7 @@ use cursive::views::{
     LinearLayout,
     Panel,
     ProgressBar,
+    TextContent,
     TextView,
 };
 use cursive::view::Nameable;
@@ -11,50 +12,63 @@ use cursive::utils::Counter;
 use cursive::Rect;
 use cursive::align::HAlign;
 use serde_json::{json, Map, Value};
+use std::time::{Duration, Instant};
 
-use super::{DeviceHandle, PANEL_WIDTH, PCType, BITS, TopView};
+use super::{DeviceHandle, PANEL_WIDTH, PCType, BITS, TopView, RollingStats};
 
-#[derive(Debug)]
-pub struct PerfCounter {
-    pub pc_type: PCType,
-    pub bits: BITS,
+/// Usage bars with min/avg/max/p95 over the stats window, one row per name.
+/// The panel of `PerfCounter`, also used for the watch list, GRBM_STATUS_SE, Engines and Activity.
+pub struct UsageBars {
+    pub names: Vec<String>,
     pub counters: Vec<Counter>,
-    pub index: Vec<(String, usize)>,
+    /// per row, usage of each refresh period over the stats window
+    pub stats: Vec<RollingStats>,
+    stats_text: Vec<TextContent>,
 }
 
-impl PerfCounter {
-    pub fn new(pc_type: PCType, s: &[(&str, usize)]) -> Self {
-        let len = s.len();
-        let mut counters: Vec<Counter> = Vec::with_capacity(len);
-        let mut index: Vec<(String, usize)>  = Vec::with_capacity(len);
-
-        for (name, idx) in s.iter() {
-            counters.push(Counter::new(0));
-            index.push((name.to_string(), *idx));
-        }
+impl UsageBars {
+    const BAR_WIDTH: usize = 20;
+    const STATS_WIDTH: usize = 17;
+    const LEFT_LEN: usize = PANEL_WIDTH - Self::BAR_WIDTH - Self::STATS_WIDTH - 3;
+
+    pub fn new(names: Vec<String>, stats_window: Duration) -> Self {
+        let counters: Vec<Counter> = names.iter().map(|_| Counter::new(0)).collect();
+        let stats = names.iter().map(|_| RollingStats::new(stats_window)).collect();
+        let stats_text = names.iter().map(|_| TextContent::new("")).collect();
 
         Self {
-            pc_type,
-            bits: BITS::default(),
+            names,
             counters,
-            index,
+            stats,
+            stats_text,
         }
     }
 
     pub fn top_view(
         &self,
+        title: &str,
         visible: bool,
     ) -> TopView {
-        const BAR_WIDTH: usize = 40;
-        const LEFT_LEN: usize = PANEL_WIDTH - BAR_WIDTH;
-        
-        let title = self.pc_type.to_string();
+        const BAR_WIDTH: usize = UsageBars::BAR_WIDTH;
+        const STATS_WIDTH: usize = UsageBars::STATS_WIDTH;
+        const LEFT_LEN: usize = UsageBars::LEFT_LEN;
+
         let mut sub_layout = LinearLayout::vertical();
         let label = |value: usize, (_, _): (usize, usize)| -> String {
             format!("[{val:^width$}]", width = BAR_WIDTH - 2, val = format!("{value:3} %"))
         };
+        let window = self.stats.first().map(|s| s.window.as_secs()).unwrap_or(0);
+
+        sub_layout.add_child(
+            TextView::new(format!(
+                "{:>w$}{:<STATS_WIDTH$}",
+                format!("last {window}s:"),
+                " min avg max p95",
+                w = LEFT_LEN + BAR_WIDTH + 3,
+            ))
+        );
 
-        for (c, (name, _)) in self.counters.iter().zip(self.index.iter()) {
+        for ((c, text), name) in self.counters.iter().zip(self.stats_text.iter()).zip(self.names.iter()) {
             sub_layout.add_child(
                 FixedLayout::new()
                     .child(
@@ -67,25 +81,108 @@ impl PerfCounter {
                             .with_value(c.clone())
                             .with_label(label)
                     )
+                    .child(
+                        Rect::from_size((LEFT_LEN+BAR_WIDTH+3,0), (STATS_WIDTH, 1)),
+                        TextView::new_with_content(text.clone()),
+                    )
             );
         }
 
         Panel::new(
             HideableView::new(sub_layout)
                 .visible(visible)
-                .with_name(&title)
+                .with_name(title)
         )
-        .title(&title)
+        .title(title)
         .title_position(HAlign::Left)
     }
 
-    pub fn set_value(&self) {
-        for (c, (_, pos)) in self.counters.iter().zip(self.index.iter()) {
-            c.set(self.bits.get(*pos) as usize)
+    /// `usage`: % of each row
+    pub fn set_value(&self, usage: &[u32]) {
+        for (c, val) in self.counters.iter().zip(usage.iter()) {
+            c.set(*val as usize)
+        }
+
+        for (text, stats) in self.stats_text.iter().zip(self.stats.iter()) {
+            let Some(s) = stats.stats() else { continue };
+
+            text.set_content(format!(
+                " {:3} {:3} {:3} {:3}",
+                s.min,
+                s.avg.round() as u32,
+                s.max,
+                s.p95,
+            ));
+        }
+    }
+
+    /// Adds the usage of the current refresh period to the stats window
+    pub fn update_stats(&mut self, usage: &[u32]) {
+        let now = Instant::now();
+
+        for (stats, val) in self.stats.iter_mut().zip(usage.iter()) {
+            stats.push(*val, now);
         }
     }
 
+    pub fn json_value(&self, row: usize, usage: impl Into<Value>) -> Value {
+        json!({
+            "usage": usage.into(),
+            "unit": "%",
+            "stats": self.stats_json_value(row),
+        })
+    }
+
+    /// `null` if no value is in the stats window yet
+    pub fn stats_json_value(&self, row: usize) -> Value {
+        let stats = &self.stats[row];
+
+        stats.stats().map(|s| s.json_value(stats.window)).into()
+    }
+}
+
+pub struct PerfCounter {
+    pub pc_type: PCType,
+    pub bits: BITS,
+    pub index: Vec<(String, usize)>,
+    pub bars: UsageBars,
+}
+
+impl PerfCounter {
+    pub fn new(pc_type: PCType, index: Vec<(String, usize)>, stats_window: Duration) -> Self {
+        let bars = UsageBars::new(index.iter().map(|(name, _)| name.clone()).collect(), stats_window);
+
+        Self {
+            pc_type,
+            bits: BITS::default(),
+            index,
+            bars,
+        }
+    }
+
+    fn usage(&self) -> Vec<u32> {
+        self.index.iter().map(|(_, pos)| self.bits.percent(*pos)).collect()
+    }
+
+    pub fn top_view(
+        &self,
+        visible: bool,
+    ) -> TopView {
+        self.bars.top_view(&self.pc_type.to_string(), visible)
+    }
+
+    pub fn set_value(&self) {
+        self.bars.set_value(&self.usage());
+    }
+
+    /// Adds the usage of the current refresh period to the stats window
+    pub fn update_stats(&mut self) {
+        let usage = self.usage();
+        self.bars.update_stats(&usage);
+    }
+
     pub fn dump(&mut self) {
+        self.update_stats();
         self.set_value();
         self.bits.clear();
     }
@@ -99,14 +196,8 @@ impl PerfCounter {
     pub fn json_value(&mut self) -> Value {
         let mut m = Map::new();
 
-        for (name, pos) in &self.index {
-            m.insert(
-                name.to_string(),
-                json!({
-                    "usage": self.bits.get(*pos),
-                    "unit": "%",
-                }),
-            );
+        for (row, ((name, _), usage)) in self.index.iter().zip(self.usage()).enumerate() {
+            m.insert(name.to_string(), self.bars.json_value(row, usage));
         }
 
         m.into()
@@ -0,0 +1,196 @@
+use super::{Opt, ProcInfo, NsInfo, FdInfoUsage, MemRegions, EngineValues, read_fdinfo};
+use std::fmt::{self, Write};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use cursive::views::{Dialog, TextView};
+use cursive::view::Scrollable;
+
+/// A DRM fd of the process
+#[derive(Clone, Debug)]
+pub struct FdDetail {
+    pub fd: i32,
+    pub client_id: usize,
+    pub usage: FdInfoUsage,
+}
+
+/// Details of a process, not truncated like the fdinfo table
+#[derive(Clone, Debug)]
+pub struct ProcDetail {
+    pub pid: i32,
+    pub name: String,
+    pub user: String,
+    pub ns: NsInfo,
+    pub cmdline: String,
+    pub exe: String,
+    pub fds: Vec<FdDetail>,
+}
+
+impl ProcDetail {
+    /// `user`: the user name of the fdinfo row
+    pub fn from_proc_info(proc_info: &ProcInfo, user: String, proc_root: &Path) -> Self {
+        let pid = proc_info.pid;
+        let pid_path = proc_root.join(pid.to_string());
+
+        // arguments are separated by '\0'
+        let cmdline = fs::read(pid_path.join("cmdline"))
+            .map(|v| {
+                String::from_utf8_lossy(&v)
+                    .split('\0')
+                    .filter(|s| !s.is_empty())
+                    .collect::<Vec<&str>>()
+                    .join(" ")
+            })
+            .unwrap_or_default();
+        let exe = fs::read_link(pid_path.join("exe"))
+            .map(|p| p.to_string_lossy().to_string())
+            .unwrap_or_default();
+
+        let mut buf = String::new();
+        let fds = proc_info.fds.iter().filter_map(|fd| {
+            let path = pid_path.join(format!("fdinfo/{fd}"));
+            let (client_id, usage) = read_fdinfo(&path, &mut buf)?;
+
+            Some(FdDetail { fd: *fd, client_id, usage })
+        }).collect();
+
+        Self {
+            pid,
+            name: proc_info.name.clone(),
+            user,
+            ns: proc_info.ns.clone(),
+            cmdline,
+            exe,
+            fds,
+        }
+    }
+
+    pub fn print(&self) -> Result<String, fmt::Error> {
+        let mut buf = String::new();
+
+        writeln!(buf, "PID:     {}", self.pid)?;
+        writeln!(buf, "Name:    {}", self.name)?;
+        writeln!(buf, "User:    {}", self.user)?;
+        writeln!(buf, "NSpid:   {:?}", self.ns.ns_pid)?;
+        writeln!(buf, "NSpgid:  {:?}", self.ns.ns_pgid)?;
+        writeln!(
+            buf,
+            "pid_ns:  {}, mnt_ns: {}",
+            self.ns.pid_ns.map(|v| v.to_string()).unwrap_or_default(),
+            self.ns.mnt_ns.map(|v| v.to_string()).unwrap_or_default(),
+        )?;
+        writeln!(buf, "cmdline: {}", self.cmdline)?;
+        writeln!(buf, "exe:     {}", self.exe)?;
+
+        for fd in &self.fds {
+            let usage = &fd.usage;
+
+            writeln!(buf, "\nfd {}, drm-client-id: {}", fd.fd, fd.client_id)?;
+            writeln!(
+                buf,
+                " Resident [KiB]: VRAM {}, GTT {}, CPU visible {}",
+                usage.vram_usage,
+                usage.gtt_usage,
+                usage.cpu_accessible_usage,
+            )?;
+
+            for (regions, label) in [
+                (&usage.total, "Total"),
+                (&usage.shared, "Shared"),
+                (&usage.active, "Active"),
+                (&usage.purgeable, "Purgeable"),
+            ] {
+                write_regions(&mut buf, label, regions)?;
+            }
+
+            writeln!(buf, " Engine [ns]:")?;
+            for (val, label) in [
+                (usage.gfx, "gfx"),
+                (usage.compute, "compute"),
+                (usage.dma, "dma"),
+                (usage.dec, "dec"),
+                (usage.enc, "enc"),
+                (usage.uvd_enc, "enc_1"),
+                (usage.vcn_jpeg, "jpeg"),
+            ] {
+                writeln!(buf, "  {label:<8}: {val:>16}")?;
+            }
+
+            for (values, label) in [
+                (&usage.engine_capacity, "Capacity"),
+                (&usage.cycles, "Cycles"),
+                (&usage.maxfreq, "Max Freq [MHz]"),
+            ] {
+                write_engine_values(&mut buf, label, values)?;
+            }
+        }
+
+        Ok(buf)
+    }
+
+    /// Shows the details of the process selected in the fdinfo table
+    pub fn cb(
+        index: Arc<Mutex<Vec<ProcInfo>>>,
+        proc_root: PathBuf,
+    ) -> impl Fn(&mut cursive::Cursive) + Send + Sync + 'static {
+        move |siv: &mut cursive::Cursive| {
+            let Some((proc_info, user)) = selected_process(siv, &index) else { return };
+            let detail = ProcDetail::from_proc_info(&proc_info, user, &proc_root);
+            let text = detail.print().unwrap_or_default();
+
+            siv.add_layer(
+                Dialog::around(TextView::new(text).scrollable())
+                    .title(format!("{} ({})", detail.name, detail.pid))
+                    .dismiss_button("Close")
+            );
+        }
+    }
+}
+
+/// The process of the row selected in the fdinfo table and its user name.
+/// Shows a message and returns `None` if no row is selected or the process is no longer in `index`.
+pub fn selected_process(siv: &mut cursive::Cursive, index: &Arc<Mutex<Vec<ProcInfo>>>) -> Option<(ProcInfo, String)> {
+    let selected = {
+        let opt = siv.user_data::<Opt>().unwrap().lock().unwrap();
+        opt.fdinfo_selected.and_then(|pid| opt.fdinfo_rows.iter().find(|(p, _)| *p == pid).cloned())
+    };
+    let Some((pid, user)) = selected else {
+        siv.add_layer(Dialog::info("No process is selected, select a row of fdinfo with Ctrl-N/Ctrl-P"));
+        return None;
+    };
+    let proc_info = index.lock().ok()?.iter().find(|p| p.pid == pid).cloned();
+    let Some(proc_info) = proc_info else {
+        siv.add_layer(Dialog::info(format!("The process ({pid}) has exited")));
+        return None;
+    };
+
+    Some((proc_info, user))
+}
+
+fn write_regions(buf: &mut String, label: &str, regions: &MemRegions) -> Result<(), fmt::Error> {
+    writeln!(
+        buf,
+        " {label:<9} [KiB]: VRAM {}, GTT {}, CPU {}",
+        regions.vram,
+        regions.gtt,
+        regions.cpu,
+    )
+}
+
+fn write_engine_values(buf: &mut String, label: &str, values: &EngineValues) -> Result<(), fmt::Error> {
+    let values: Vec<String> = [
+        (values.gfx, "gfx"),
+        (values.compute, "compute"),
+        (values.dma, "dma"),
+        (values.dec, "dec"),
+        (values.enc, "enc"),
+        (values.uvd_enc, "enc_1"),
+        (values.vcn_jpeg, "jpeg"),
+    ]
+    .iter()
+    .filter(|(val, _)| *val != 0)
+    .map(|(val, name)| format!("{name} {val}"))
+    .collect();
+
+    writeln!(buf, " {label}: {}", values.join(", "))
+}
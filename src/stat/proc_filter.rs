@@ -0,0 +1,211 @@
+use super::ProcInfo;
+use std::fmt;
+use std::fs;
+use std::str::FromStr;
+
+const PASSWD_PATH: &str = "/etc/passwd";
+
+/// Filter of the fdinfo process table
+/// "pid:<PID>", "user:<name or UID>", otherwise a pattern of the process name
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ProcFilter {
+    Pid(i32),
+    Uid(u32),
+    Name(String),
+}
+
+impl ProcFilter {
+    pub fn is_match(&self, proc_info: &ProcInfo) -> bool {
+        match self {
+            Self::Pid(pid) => proc_info.pid == *pid,
+            Self::Uid(uid) => proc_info.uid == Some(*uid),
+            Self::Name(pat) => {
+                let re: Vec<char> = pat.to_lowercase().chars().collect();
+                let text: Vec<char> = proc_info.name.to_lowercase().chars().collect();
+
+                regex_match(&re, &text)
+            },
+        }
+    }
+}
+
+impl FromStr for ProcFilter {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+
+        if let Some(pid) = s.strip_prefix("pid:") {
+            let pid = pid.parse::<i32>().map_err(|err| format!("Invalid PID: {pid} ({err})"))?;
+
+            return Ok(Self::Pid(pid));
+        }
+
+        if let Some(user) = s.strip_prefix("user:") {
+            let uid = user.parse::<u32>().ok()
+                .or_else(|| get_uid_by_name(user))
+                .ok_or_else(|| format!("Unknown user: {user}"))?;
+
+            return Ok(Self::Uid(uid));
+        }
+
+        Ok(Self::Name(s.to_string()))
+    }
+}
+
+impl fmt::Display for ProcFilter {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Pid(pid) => write!(f, "pid:{pid}"),
+            Self::Uid(uid) => write!(f, "user:{uid}"),
+            Self::Name(pat) => write!(f, "{pat}"),
+        }
+    }
+}
+
+/// Returns (name, UID) pairs of "/etc/passwd"
+pub fn read_passwd() -> Vec<(String, u32)> {
+    let Ok(s) = fs::read_to_string(PASSWD_PATH) else { return Vec::new() };
+
+    s.lines().filter_map(|l| {
+        // name:password:UID:GID:GECOS:directory:shell
+        let mut split = l.split(':');
+        let name = split.next()?;
+        let uid = split.nth(1)?.parse::<u32>().ok()?;
+
+        Some((name.to_string(), uid))
+    }).collect()
+}
+
+fn get_uid_by_name(name: &str) -> Option<u32> {
+    read_passwd().into_iter().find(|(n, _)| n == name).map(|(_, uid)| uid)
+}
+
+/// Matches a subset of regular expressions: `^`, `$`, `.` and `*`.
+/// A pattern without them is a substring match.
+/// ref: Rob Pike's matcher in "The Practice of Programming"
+fn regex_match(re: &[char], text: &[char]) -> bool {
+    if let Some(('^', re)) = re.split_first() {
+        return match_here(re, text);
+    }
+
+    let mut text = text;
+
+    loop {
+        if match_here(re, text) { return true }
+        let Some((_, rest)) = text.split_first() else { return false };
+        text = rest;
+    }
+}
+
+fn match_here(re: &[char], text: &[char]) -> bool {
+    match re {
+        [] => true,
+        [c, '*', rest @ ..] => match_star(*c, rest, text),
+        ['$'] => text.is_empty(),
+        [c, rest @ ..] => match text.split_first() {
+            Some((t, text)) if *c == '.' || c == t => match_here(rest, text),
+            _ => false,
+        },
+    }
+}
+
+fn match_star(c: char, re: &[char], text: &[char]) -> bool {
+    let mut text = text;
+
+    loop {
+        if match_here(re, text) { return true }
+
+        match text.split_first() {
+            Some((t, rest)) if c == '.' || *t == c => text = rest,
+            _ => return false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn is_match(re: &str, text: &str) -> bool {
+        let re: Vec<char> = re.chars().collect();
+        let text: Vec<char> = text.chars().collect();
+
+        regex_match(&re, &text)
+    }
+
+    fn proc_info(pid: i32, name: &str) -> ProcInfo {
+        ProcInfo { pid, name: name.to_string(), ..Default::default() }
+    }
+
+    #[test]
+    fn substring() {
+        assert!(is_match("gears", "glxgears"));
+        assert!(is_match("", "glxgears"));
+        assert!(is_match("", ""));
+        assert!(!is_match("gearz", "glxgears"));
+        assert!(!is_match("glxgears", "gears"));
+    }
+
+    #[test]
+    fn anchors() {
+        assert!(is_match("^glx", "glxgears"));
+        assert!(!is_match("^gears", "glxgears"));
+        assert!(is_match("gears$", "glxgears"));
+        assert!(!is_match("glx$", "glxgears"));
+        assert!(is_match("^glxgears$", "glxgears"));
+        assert!(!is_match("^glxgear$", "glxgears"));
+        assert!(is_match("^$", ""));
+    }
+
+    #[test]
+    fn dot_and_star() {
+        assert!(is_match("g.x", "glxgears"));
+        assert!(!is_match("^g.x$", "glxgears"));
+        assert!(is_match("^g.*s$", "glxgears"));
+        assert!(is_match("^glxx*gears$", "glxgears"));
+        assert!(is_match("^gl*xgears$", "gxgears"));
+        assert!(is_match("^.*$", ""));
+        assert!(!is_match("^a*b$", "aaac"));
+    }
+
+    #[test]
+    fn name_filter_ignores_case() {
+        let filter: ProcFilter = "^Xorg$".parse().unwrap();
+
+        assert_eq!(filter, ProcFilter::Name("^Xorg$".to_string()));
+        assert!(filter.is_match(&proc_info(1, "Xorg")));
+        assert!(filter.is_match(&proc_info(1, "xorg")));
+        assert!(!filter.is_match(&proc_info(1, "Xorg.bin")));
+    }
+
+    #[test]
+    fn pid_prefix() {
+        let filter: ProcFilter = " pid:1234 ".parse().unwrap();
+
+        assert_eq!(filter, ProcFilter::Pid(1234));
+        assert_eq!(filter.to_string(), "pid:1234");
+        assert!(filter.is_match(&proc_info(1234, "glxgears")));
+        assert!(!filter.is_match(&proc_info(123, "1234")));
+
+        assert!("pid:".parse::<ProcFilter>().is_err());
+        assert!("pid:abc".parse::<ProcFilter>().is_err());
+    }
+
+    #[test]
+    fn user_prefix() {
+        let filter: ProcFilter = "user:1000".parse().unwrap();
+        let mut p = proc_info(1, "glxgears");
+
+        assert_eq!(filter, ProcFilter::Uid(1000));
+        assert_eq!(filter.to_string(), "user:1000");
+        assert!(!filter.is_match(&p));
+        p.uid = Some(1000);
+        assert!(filter.is_match(&p));
+        p.uid = Some(0);
+        assert!(!filter.is_match(&p));
+
+        assert!("user:".parse::<ProcFilter>().is_err());
+        assert!("user:no-such-user-amdgpu_top".parse::<ProcFilter>().is_err());
+    }
+}
@@ -0,0 +1,111 @@
+use super::FdInfoUsage;
+use std::time::{Duration, Instant};
+use serde_json::{json, Value};
+
+/// Cumulative engine time, ns
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct EngineTime {
+    pub gfx: u64,
+    pub compute: u64,
+    pub dma: u64,
+    pub dec: u64,
+    pub enc: u64,
+    pub jpeg: u64,
+}
+
+impl EngineTime {
+    /// Engine time consumed between `pre` and `cur`.
+    /// A counter that goes backwards (e.g. a DRM client was closed) counts as 0.
+    pub fn from_delta(cur: &FdInfoUsage, pre: &FdInfoUsage) -> Self {
+        let [gfx, compute, dma, dec, enc, jpeg] = [
+            (cur.gfx, pre.gfx),
+            (cur.compute, pre.compute),
+            (cur.dma, pre.dma),
+            (cur.dec, pre.dec),
+            (cur.enc + cur.uvd_enc, pre.enc + pre.uvd_enc),
+            (cur.vcn_jpeg, pre.vcn_jpeg),
+        ].map(|(cur, pre)| cur.saturating_sub(pre).max(0) as u64);
+
+        Self { gfx, compute, dma, dec, enc, jpeg }
+    }
+
+    pub fn media(&self) -> u64 {
+        self.dec + self.enc + self.jpeg
+    }
+}
+
+impl std::ops::AddAssign for EngineTime {
+    fn add_assign(&mut self, other: Self) {
+        self.gfx += other.gfx;
+        self.compute += other.compute;
+        self.dma += other.dma;
+        self.dec += other.dec;
+        self.enc += other.enc;
+        self.jpeg += other.jpeg;
+    }
+}
+
+/// GPU usage of a process since it was first seen
+#[derive(Clone, Debug)]
+pub struct ProcHistory {
+    pub pid: i32,
+    pub name: String,
+    pub first_seen: Instant,
+    pub last_seen: Instant,
+    pub engine: EngineTime,
+    pub peak_vram: u64, // KiB
+}
+
+impl ProcHistory {
+    pub fn new(pid: i32, name: &str, now: Instant) -> Self {
+        Self {
+            pid,
+            name: name.to_string(),
+            first_seen: now,
+            last_seen: now,
+            engine: EngineTime::default(),
+            peak_vram: 0,
+        }
+    }
+
+    pub fn update(&mut self, engine: EngineTime, vram_usage: u64, now: Instant) {
+        self.engine += engine;
+        self.peak_vram = self.peak_vram.max(vram_usage);
+        self.last_seen = now;
+    }
+
+    pub fn lifetime(&self) -> Duration {
+        self.last_seen.duration_since(self.first_seen)
+    }
+
+    pub fn json_value(&self) -> Value {
+        json!({
+            "pid": self.pid,
+            "name": self.name,
+            "lifetime": {
+                "value": self.lifetime().as_millis(),
+                "unit": "ms",
+            },
+            "peak VRAM Usage": {
+                "value": self.peak_vram >> 10,
+                "unit": "MiB",
+            },
+            "GPU Time": {
+                "GFX": self.engine.gfx,
+                "Compute": self.engine.compute,
+                "DMA": self.engine.dma,
+                "DEC": self.engine.dec,
+                "ENC": self.engine.enc,
+                "JPEG": self.engine.jpeg,
+                "unit": "ns",
+            },
+        })
+    }
+}
+
+/// e.g. "01:02:03"
+pub fn fmt_duration(d: Duration) -> String {
+    let secs = d.as_secs();
+
+    format!("{:02}:{:02}:{:02}", secs / 3600, (secs / 60) % 60, secs % 60)
+}
@@ -0,0 +1,250 @@
+use super::{ProcInfo, KfdCollector, get_all_processes, get_fds};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Every `FULL_SCAN_CYCLE` updates, the fds of all processes are walked again
+/// to find processes that opened the device after they were first seen.
+/// Between them, only new PIDs are walked.
+const FULL_SCAN_CYCLE: usize = 10;
+
+/// Index of processes using the device, updated incrementally.
+/// Only new PIDs are scanned between full scans, known GPU processes are revalidated
+/// on every update, and walked again if their number of fds has changed.
+/// KFD processes are indexed even without DRM fds.
+pub struct ProcIndex {
+    proc_root: PathBuf,
+    device_nodes: Vec<String>,
+    self_pid: i32,
+    /// PIDs scanned at least once, still alive at the last update
+    seen: HashSet<i32>,
+    /// number of fds of the indexed processes at the last update
+    fd_counts: HashMap<i32, usize>,
+    cycle: usize,
+    pub kfd: Option<KfdCollector>,
+    pub procs: Vec<ProcInfo>,
+}
+
+impl ProcIndex {
+    pub fn new(proc_root: &Path, device_nodes: &[String], self_pid: i32) -> Self {
+        Self {
+            proc_root: proc_root.to_path_buf(),
+            device_nodes: device_nodes.to_vec(),
+            self_pid,
+            seen: HashSet::new(),
+            fd_counts: HashMap::new(),
+            cycle: 0,
+            kfd: None,
+            procs: Vec::new(),
+        }
+    }
+
+    pub fn update(&mut self) {
+        let full_scan = self.cycle == 0;
+        self.cycle = (self.cycle + 1) % FULL_SCAN_CYCLE;
+
+        let pids: HashSet<i32> = get_all_processes(&self.proc_root).into_iter().collect();
+        let kfd_pids: HashSet<i32> = self.kfd.as_ref()
+            .map(|kfd| kfd.pids().into_iter().collect())
+            .unwrap_or_default();
+        let is_gpu_proc = |pid: i32, fds: &[i32]| !fds.is_empty() || kfd_pids.contains(&pid);
+
+        // exited processes
+        self.seen.retain(|pid| pids.contains(pid));
+        self.procs.retain(|p| pids.contains(&p.pid));
+
+        for p in self.procs.iter_mut() {
+            let fd_count = get_fd_count(p.pid, &self.proc_root);
+
+            if full_scan || self.fd_counts.get(&p.pid) != Some(&fd_count) {
+                p.fds = get_fds(p.pid, &self.proc_root, &self.device_nodes);
+            } else {
+                p.fds.retain(|fd| is_drm_fd(p.pid, *fd, &self.proc_root, &self.device_nodes));
+
+                // the fds may have been reopened
+                if p.fds.is_empty() {
+                    p.fds = get_fds(p.pid, &self.proc_root, &self.device_nodes);
+                }
+            }
+
+            self.fd_counts.insert(p.pid, fd_count);
+        }
+
+        self.procs.retain(|p| is_gpu_proc(p.pid, &p.fds));
+
+        let known: HashSet<i32> = self.procs.iter().map(|p| p.pid).collect();
+        self.fd_counts.retain(|pid, _| known.contains(pid));
+
+        for pid in pids {
+            if pid == self.self_pid || known.contains(&pid) { continue }
+            // idle processes are walked again on the next full scan
+            if !full_scan && self.seen.contains(&pid) && !kfd_pids.contains(&pid) { continue }
+
+            self.seen.insert(pid);
+
+            let fds = get_fds(pid, &self.proc_root, &self.device_nodes);
+
+            if !is_gpu_proc(pid, &fds) { continue }
+
+            if let Some(proc_info) = ProcInfo::with_fds(pid, &self.proc_root, fds) {
+                self.fd_counts.insert(pid, get_fd_count(pid, &self.proc_root));
+                self.procs.push(proc_info);
+            }
+        }
+    }
+}
+
+/// Counting the entries is much cheaper than reading the links of all fds.
+fn get_fd_count(pid: i32, proc_root: &Path) -> usize {
+    fs::read_dir(proc_root.join(format!("{pid}/fd"))).map(|dir| dir.count()).unwrap_or(0)
+}
+
+fn is_drm_fd(pid: i32, fd: i32, proc_root: &Path, device_nodes: &[String]) -> bool {
+    let Ok(link) = fs::read_link(proc_root.join(format!("{pid}/fd/{fd}"))) else { return false };
+
+    device_nodes.iter().any(|node| link.starts_with(node))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stat::fake_proc::FakeProc;
+    use std::time::{Duration, Instant};
+
+    const RENDER: &str = "/dev/dri/renderD128";
+    const FDINFO: &str = "drm-client-id:\t1\n";
+
+    fn pids(index: &ProcIndex) -> Vec<i32> {
+        let mut pids: Vec<i32> = index.procs.iter().map(|p| p.pid).collect();
+        pids.sort();
+
+        pids
+    }
+
+    #[test]
+    fn new_and_exited_processes() {
+        let proc = FakeProc::new();
+        let mut index = ProcIndex::new(proc.path(), &[RENDER.to_string()], 1);
+
+        proc.add_proc(1, "amdgpu_top");
+        proc.add_fd(1, 3, RENDER, FDINFO);
+        proc.add_proc(200, "vkcube");
+        proc.add_fd(200, 3, RENDER, FDINFO);
+
+        index.update();
+        assert_eq!(pids(&index), [200]);
+
+        // not a full scan
+        proc.add_proc(100, "glxgears");
+        proc.add_fd(100, 5, RENDER, FDINFO);
+        index.update();
+        assert_eq!(pids(&index), [100, 200]);
+        assert_eq!(index.procs.iter().find(|p| p.pid == 100).unwrap().fds, [5]);
+
+        proc.remove_fd(100, 5);
+        proc.remove_proc(200);
+        index.update();
+        assert!(pids(&index).is_empty());
+        assert!(!index.seen.contains(&200));
+    }
+
+    #[test]
+    fn known_process_opens_another_fd() {
+        let proc = FakeProc::new();
+        let mut index = ProcIndex::new(proc.path(), &[RENDER.to_string()], 1);
+
+        proc.add_proc(100, "glxgears");
+        proc.add_fd(100, 3, RENDER, FDINFO);
+        index.update();
+
+        proc.add_fd(100, 4, RENDER, FDINFO);
+        index.update();
+
+        let mut fds = index.procs[0].fds.clone();
+        fds.sort();
+        assert_eq!(fds, [3, 4]);
+
+        proc.remove_fd(100, 3);
+        index.update();
+        assert_eq!(index.procs[0].fds, [4]);
+    }
+
+    #[test]
+    fn idle_process_is_found_by_full_scan() {
+        let proc = FakeProc::new();
+        let mut index = ProcIndex::new(proc.path(), &[RENDER.to_string()], 1);
+
+        proc.add_proc(100, "glxgears");
+        proc.add_fd(100, 3, "/dev/null", "");
+        index.update();
+
+        proc.add_fd(100, 4, RENDER, FDINFO);
+
+        for _ in 1..FULL_SCAN_CYCLE {
+            index.update();
+            assert!(pids(&index).is_empty());
+        }
+
+        index.update();
+        assert_eq!(pids(&index), [100]);
+    }
+
+    /// cargo test --release proc_index -- --ignored --nocapture
+    #[test]
+    #[ignore]
+    fn bench_update() {
+        const PROCS: i32 = 2000;
+        const FDS: i32 = 32;
+        const GPU_PROCS: i32 = 20;
+        const UPDATES: u32 = FULL_SCAN_CYCLE as u32 * 10;
+
+        let proc = FakeProc::new();
+
+        for pid in 2..PROCS+2 {
+            proc.add_proc(pid, "proc");
+
+            for fd in 0..FDS {
+                proc.add_fd(pid, fd, "/dev/null", "");
+            }
+
+            if pid % (PROCS / GPU_PROCS) == 0 {
+                proc.add_fd(pid, FDS, RENDER, FDINFO);
+            }
+        }
+
+        let mut index = ProcIndex::new(proc.path(), &[RENDER.to_string()], 1);
+
+        let now = Instant::now();
+        index.update();
+        let full_scan = now.elapsed();
+        assert_eq!(index.procs.len(), GPU_PROCS as usize);
+
+        let (mut full_scans, mut incremental) = (Duration::ZERO, Duration::ZERO);
+        for _ in 0..UPDATES {
+            let full_scan = index.cycle == 0;
+            let now = Instant::now();
+            index.update();
+
+            if full_scan { full_scans += now.elapsed() } else { incremental += now.elapsed() }
+        }
+        let average = (full_scans + incremental) / UPDATES;
+        let full_scans = full_scans / (UPDATES / FULL_SCAN_CYCLE as u32);
+        let incremental = incremental / (UPDATES - UPDATES / FULL_SCAN_CYCLE as u32);
+
+        let now = Instant::now();
+        for _ in 0..UPDATES / 10 {
+            let fds: Vec<Vec<i32>> = get_all_processes(proc.path()).into_iter()
+                .map(|pid| get_fds(pid, proc.path(), &[RENDER.to_string()]))
+                .collect();
+            assert_eq!(fds.len(), PROCS as usize);
+        }
+        let walk_all = now.elapsed() / (UPDATES / 10);
+
+        println!("{PROCS} processes, {FDS} fds each, {GPU_PROCS} using the device");
+        println!("first update:              {full_scan:?}");
+        println!("full scan:                 {full_scans:?}");
+        println!("update between full scans: {incremental:?}");
+        println!("update (average):          {average:?}");
+        println!("walking all fds (before):  {walk_all:?}");
+    }
+}
@@ -0,0 +1,187 @@
+use super::get_all_processes;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+
+/// PID namespace info of a process, from "/proc/<pid>/status" and "/proc/<pid>/ns/"
+#[derive(Clone, Debug, Default, Eq, PartialEq, PartialOrd)]
+pub struct NsInfo {
+    /// PID in each nested PID namespace, from the outermost to the innermost
+    pub ns_pid: Vec<i32>,
+    pub ns_pgid: Vec<i32>,
+    /// inode number of "ns/pid"
+    pub pid_ns: Option<u64>,
+    /// inode number of "ns/mnt"
+    pub mnt_ns: Option<u64>,
+}
+
+impl NsInfo {
+    pub fn from_pid(pid: i32, proc_root: &Path) -> Self {
+        let status = fs::read_to_string(proc_root.join(format!("{pid}/status"))).unwrap_or_default();
+
+        Self {
+            ns_pid: parse_ns_ids(&status, "NSpid:"),
+            ns_pgid: parse_ns_ids(&status, "NSpgid:"),
+            pid_ns: get_ns_inode(pid, proc_root, "pid"),
+            mnt_ns: get_ns_inode(pid, proc_root, "mnt"),
+        }
+    }
+
+    /// PID in the innermost namespace, `None` if the process is not in a nested PID namespace
+    pub fn inner_pid(&self) -> Option<i32> {
+        if 1 < self.ns_pid.len() { self.ns_pid.last().copied() } else { None }
+    }
+
+    pub fn inner_pgid(&self) -> Option<i32> {
+        if 1 < self.ns_pgid.len() { self.ns_pgid.last().copied() } else { None }
+    }
+}
+
+/// "NSpid:\t<pid>\t<pid in the child namespace>.."
+fn parse_ns_ids(status: &str, key: &str) -> Vec<i32> {
+    let Some(ids) = status.lines().find_map(|l| l.strip_prefix(key)) else { return Vec::new() };
+
+    ids.split_whitespace().filter_map(|id| id.parse().ok()).collect()
+}
+
+/// "pid:[4026531836]" -> 4026531836
+fn get_ns_inode(pid: i32, proc_root: &Path, ns: &str) -> Option<u64> {
+    let link = fs::read_link(proc_root.join(format!("{pid}/ns/{ns}"))).ok()?;
+
+    parse_ns_inode(link.to_str()?)
+}
+
+fn parse_ns_inode(s: &str) -> Option<u64> {
+    let s = s.find('[')
+        .and_then(|pos| s[pos+1..].strip_suffix(']'))
+        .unwrap_or(s);
+
+    s.parse().ok()
+}
+
+/// `-p` option, "<PID>" or "<PID namespace>:<PID>".
+/// The namespace is the inode number of "/proc/<pid>/ns/pid" (e.g. "4026532451" or "pid:[4026532451]").
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PidSelector {
+    pub pid_ns: Option<u64>,
+    pub pid: i32,
+}
+
+impl PidSelector {
+    /// Returns the PID seen from `proc_root`
+    pub fn resolve(&self, proc_root: &Path) -> Option<i32> {
+        let Some(pid_ns) = self.pid_ns else { return Some(self.pid) };
+
+        get_all_processes(proc_root).into_iter().find(|pid| {
+            let ns = NsInfo::from_pid(*pid, proc_root);
+
+            ns.pid_ns == Some(pid_ns) && ns.ns_pid.last() == Some(&self.pid)
+        })
+    }
+}
+
+impl FromStr for PidSelector {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (pid_ns, pid) = match s.rsplit_once(':') {
+            Some((ns, pid)) => {
+                let ns = parse_ns_inode(ns).ok_or_else(|| format!("Invalid PID namespace: {ns}"))?;
+
+                (Some(ns), pid)
+            },
+            None => (None, s),
+        };
+        let pid = pid.parse::<i32>().map_err(|err| format!("Invalid PID: {pid} ({err})"))?;
+
+        Ok(Self { pid_ns, pid })
+    }
+}
+
+impl fmt::Display for PidSelector {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.pid_ns {
+            Some(pid_ns) => write!(f, "{pid_ns}:{}", self.pid),
+            None => write!(f, "{}", self.pid),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stat::fake_proc::FakeProc;
+
+    #[test]
+    fn ns_ids() {
+        let status = "Name:\tapp\nNSpid:\t12345\t42\t1\nNSpgid:\t12340\t40\n";
+
+        assert_eq!(parse_ns_ids(status, "NSpid:"), [12345, 42, 1]);
+        assert_eq!(parse_ns_ids(status, "NSpgid:"), [12340, 40]);
+        assert!(parse_ns_ids(status, "NSsid:").is_empty());
+        assert!(parse_ns_ids("", "NSpid:").is_empty());
+        // invalid values are skipped
+        assert_eq!(parse_ns_ids("NSpid:\t12345\tx\n", "NSpid:"), [12345]);
+    }
+
+    #[test]
+    fn inner_pid() {
+        let ns = NsInfo { ns_pid: vec![12345, 42], ns_pgid: vec![12340], ..Default::default() };
+
+        assert_eq!(ns.inner_pid(), Some(42));
+        assert_eq!(ns.inner_pgid(), None);
+        assert_eq!(NsInfo::default().inner_pid(), None);
+    }
+
+    #[test]
+    fn ns_inode() {
+        assert_eq!(parse_ns_inode("pid:[4026531836]"), Some(4026531836));
+        assert_eq!(parse_ns_inode("4026531836"), Some(4026531836));
+        assert_eq!(parse_ns_inode("pid:[4026531836"), None);
+        assert_eq!(parse_ns_inode("pid:[]"), None);
+        assert_eq!(parse_ns_inode(""), None);
+    }
+
+    #[test]
+    fn pid_selector() {
+        assert_eq!("1234".parse(), Ok(PidSelector { pid_ns: None, pid: 1234 }));
+        assert_eq!("4026532451:42".parse(), Ok(PidSelector { pid_ns: Some(4026532451), pid: 42 }));
+        assert_eq!("pid:[4026532451]:42".parse(), Ok(PidSelector { pid_ns: Some(4026532451), pid: 42 }));
+
+        for (s, err) in [
+            ("", "Invalid PID: "),
+            ("abc", "Invalid PID: abc"),
+            ("4026532451:", "Invalid PID: "),
+            ("ns:42", "Invalid PID namespace: ns"),
+            (":42", "Invalid PID namespace: "),
+        ] {
+            let Err(e) = s.parse::<PidSelector>() else { panic!("{s:?} is accepted") };
+            assert!(e.starts_with(err), "{s:?}: {e}");
+        }
+
+        for s in ["1234", "4026532451:42"] {
+            assert_eq!(s.parse::<PidSelector>().unwrap().to_string(), s);
+        }
+    }
+
+    #[test]
+    fn resolve_pid_selector() {
+        let proc = FakeProc::new();
+        for (pid, ns_pid, ns) in [(100, 1, 4026532451u64), (200, 1, 4026532452), (300, 300, 4026531836)] {
+            proc.add_proc(pid, "app");
+            fs::write(proc.path().join(format!("{pid}/status")), format!("NSpid:\t{pid}\t{ns_pid}\n")).unwrap();
+            fs::create_dir_all(proc.path().join(format!("{pid}/ns"))).unwrap();
+            std::os::unix::fs::symlink(format!("pid:[{ns}]"), proc.path().join(format!("{pid}/ns/pid"))).unwrap();
+        }
+
+        let resolve = |s: &str| s.parse::<PidSelector>().unwrap().resolve(proc.path());
+
+        assert_eq!(resolve("4026532452:1"), Some(200));
+        assert_eq!(resolve("4026532451:1"), Some(100));
+        assert_eq!(resolve("4026532451:2"), None);
+        assert_eq!(resolve("4026539999:1"), None);
+        // a bare PID is not looked up
+        assert_eq!(resolve("1234"), Some(1234));
+    }
+}
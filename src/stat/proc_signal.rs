@@ -0,0 +1,152 @@
+use super::{ProcInfo, PROC_ROOT, selected_process, get_fds};
+use std::fmt;
+use std::io;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use cursive::views::{Dialog, SelectView};
+
+/// Signals sent from the fdinfo panel.
+/// Only for the host procfs (`PROC_ROOT`), PIDs of another `--proc-root` are not the ones of kill(2).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Signal {
+    Term,
+    Kill,
+    Stop,
+    Cont,
+}
+
+impl Signal {
+    const ALL: [Self; 4] = [Self::Term, Self::Kill, Self::Stop, Self::Cont];
+
+    fn as_raw(&self) -> libc::c_int {
+        match self {
+            Self::Term => libc::SIGTERM,
+            Self::Kill => libc::SIGKILL,
+            Self::Stop => libc::SIGSTOP,
+            Self::Cont => libc::SIGCONT,
+        }
+    }
+
+    pub fn send(&self, pid: i32) -> io::Result<()> {
+        // 0 and negative values are process groups for kill(2)
+        if pid <= 0 {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("Invalid PID: {pid}")));
+        }
+
+        let ret = unsafe { libc::kill(pid, self.as_raw()) };
+
+        if ret == 0 { Ok(()) } else { Err(io::Error::last_os_error()) }
+    }
+
+    /// Sends the signal only if the PID is still the selected process,
+    /// the PID may have been reused while the dialogs were open.
+    pub fn send_to(&self, proc_info: &ProcInfo, device_nodes: &[String]) -> io::Result<()> {
+        if !is_same_process(proc_info, Path::new(PROC_ROOT), device_nodes) {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                "the process has exited or no longer uses the device",
+            ));
+        }
+
+        self.send(proc_info.pid)
+    }
+
+    /// Selects a signal for the process selected in the fdinfo table, then sends it after confirmation
+    pub fn cb(
+        index: Arc<Mutex<Vec<ProcInfo>>>,
+        device_nodes: Vec<String>,
+    ) -> impl Fn(&mut cursive::Cursive) + Send + Sync + 'static {
+        move |siv: &mut cursive::Cursive| {
+            let Some((proc_info, _)) = selected_process(siv, &index) else { return };
+            let title = format!("Send signal to {} ({})", proc_info.name, proc_info.pid);
+            let device_nodes = device_nodes.clone();
+            let select = SelectView::<Signal>::new()
+                .with_all(Self::ALL.map(|sig| (sig.to_string(), sig)))
+                .on_submit(move |siv: &mut cursive::Cursive, sig: &Signal| {
+                    siv.pop_layer();
+                    confirm(siv, *sig, proc_info.clone(), device_nodes.clone());
+                });
+
+            siv.add_layer(
+                Dialog::around(select)
+                    .title(title)
+                    .dismiss_button("Cancel")
+            );
+        }
+    }
+}
+
+impl fmt::Display for Signal {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match self {
+            Self::Term => "SIGTERM",
+            Self::Kill => "SIGKILL",
+            Self::Stop => "SIGSTOP",
+            Self::Cont => "SIGCONT",
+        };
+
+        write!(f, "{s}")
+    }
+}
+
+fn confirm(siv: &mut cursive::Cursive, sig: Signal, proc_info: ProcInfo, device_nodes: Vec<String>) {
+    let (pid, name) = (proc_info.pid, proc_info.name.clone());
+
+    siv.add_layer(
+        Dialog::text(format!("Send {sig} to {name} ({pid})?"))
+            .button("Send", move |siv: &mut cursive::Cursive| {
+                siv.pop_layer();
+
+                // e.g. EPERM for a process of another user
+                if let Err(err) = sig.send_to(&proc_info, &device_nodes) {
+                    siv.add_layer(Dialog::info(format!("Failed to send {sig} to {pid}: {err}")));
+                }
+            })
+            .dismiss_button("Cancel")
+    );
+}
+
+/// The process has the same name as when it was selected, and still holds a DRM fd of the device
+fn is_same_process(proc_info: &ProcInfo, proc_root: &Path, device_nodes: &[String]) -> bool {
+    proc_info.is_alive(proc_root) && !get_fds(proc_info.pid, proc_root, device_nodes).is_empty()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stat::fake_proc::FakeProc;
+
+    const RENDER: &str = "/dev/dri/renderD128";
+
+    #[test]
+    fn same_process() {
+        let proc = FakeProc::new();
+        let device_nodes = [RENDER.to_string()];
+        let proc_info = ProcInfo { pid: 100, name: "glxgears".to_string(), ..Default::default() };
+
+        proc.add_proc(100, "glxgears");
+        proc.add_fd(100, 3, RENDER, "");
+        assert!(is_same_process(&proc_info, proc.path(), &device_nodes));
+
+        // closed the device
+        proc.remove_fd(100, 3);
+        proc.add_fd(100, 3, "/dev/null", "");
+        assert!(!is_same_process(&proc_info, proc.path(), &device_nodes));
+
+        // the PID is reused by another process using the device
+        proc.remove_proc(100);
+        proc.add_proc(100, "vkcube");
+        proc.add_fd(100, 3, RENDER, "");
+        assert!(!is_same_process(&proc_info, proc.path(), &device_nodes));
+
+        proc.remove_proc(100);
+        assert!(!is_same_process(&proc_info, proc.path(), &device_nodes));
+    }
+
+    #[test]
+    fn process_groups_are_rejected() {
+        for pid in [0, -1] {
+            assert_eq!(Signal::Term.send(pid).unwrap_err().kind(), io::ErrorKind::InvalidInput);
+        }
+    }
+}
@@ -0,0 +1,148 @@
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+use serde_json::{json, Value};
+
+/// max number of samples kept regardless of the window (e.g. with a refresh period of 0 ms)
+const MAX_SAMPLES: usize = 10_000;
+
+/// min/avg/max/p95 of the values over the window
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Stats {
+    pub min: u32,
+    pub avg: f64,
+    pub max: u32,
+    pub p95: u32,
+}
+
+impl Stats {
+    pub fn json_value(&self, window: Duration) -> Value {
+        json!({
+            "window": window.as_secs(),
+            "min": self.min,
+            "avg": (self.avg * 10.0).round() / 10.0,
+            "max": self.max,
+            "p95": self.p95,
+        })
+    }
+}
+
+/// Values of a counter over the last `window`
+#[derive(Clone, Debug)]
+pub struct RollingStats {
+    pub window: Duration,
+    samples: VecDeque<(Instant, u32)>,
+}
+
+impl RollingStats {
+    pub fn new(window: Duration) -> Self {
+        Self {
+            window,
+            samples: VecDeque::new(),
+        }
+    }
+
+    pub fn push(&mut self, value: u32, now: Instant) {
+        self.samples.push_back((now, value));
+
+        while let Some((time, _)) = self.samples.front() {
+            if now.duration_since(*time) <= self.window && self.samples.len() <= MAX_SAMPLES { break }
+            self.samples.pop_front();
+        }
+    }
+
+    pub fn stats(&self) -> Option<Stats> {
+        if self.samples.is_empty() { return None }
+
+        let mut values: Vec<u32> = self.samples.iter().map(|(_, v)| *v).collect();
+        values.sort_unstable();
+
+        let n = values.len();
+        let sum: u64 = values.iter().map(|v| *v as u64).sum();
+        // nearest-rank method
+        let p95 = values[(n * 95).div_ceil(100) - 1];
+
+        Some(Stats {
+            min: values[0],
+            avg: sum as f64 / n as f64,
+            max: values[n - 1],
+            p95,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stats_of(values: &[u32]) -> Stats {
+        let mut rs = RollingStats::new(Duration::from_secs(60));
+        let now = Instant::now();
+
+        for v in values {
+            rs.push(*v, now);
+        }
+
+        rs.stats().unwrap()
+    }
+
+    #[test]
+    fn empty() {
+        assert_eq!(RollingStats::new(Duration::from_secs(60)).stats(), None);
+    }
+
+    #[test]
+    fn min_avg_max() {
+        let s = stats_of(&[30, 10, 20]);
+
+        assert_eq!((s.min, s.max), (10, 30));
+        assert_eq!(s.avg, 20.0);
+    }
+
+    #[test]
+    fn p95_nearest_rank() {
+        // rank = ceil(0.95 * n)
+        assert_eq!(stats_of(&[42]).p95, 42);
+        assert_eq!(stats_of(&[1, 2]).p95, 2);
+        let values: Vec<u32> = (1..=20).rev().collect();
+        assert_eq!(stats_of(&values).p95, 19);
+        let values: Vec<u32> = (1..=21).collect();
+        assert_eq!(stats_of(&values).p95, 20);
+        let values: Vec<u32> = (0..100).collect();
+        assert_eq!(stats_of(&values).p95, 94);
+        let values: Vec<u32> = (0..101).collect();
+        assert_eq!(stats_of(&values).p95, 95);
+    }
+
+    #[test]
+    fn window_eviction() {
+        let mut rs = RollingStats::new(Duration::from_secs(10));
+        let base = Instant::now();
+
+        rs.push(100, base);
+        rs.push(50, base + Duration::from_secs(5));
+        rs.push(0, base + Duration::from_secs(10));
+        // exactly the window, still kept
+        assert_eq!(rs.stats().unwrap().max, 100);
+
+        rs.push(0, base + Duration::from_secs(11));
+        let s = rs.stats().unwrap();
+        assert_eq!((s.min, s.max), (0, 50));
+
+        rs.push(10, base + Duration::from_secs(30));
+        let s = rs.stats().unwrap();
+        assert_eq!((s.min, s.max, s.p95), (10, 10, 10));
+    }
+
+    #[test]
+    fn max_samples() {
+        let mut rs = RollingStats::new(Duration::from_secs(3600));
+        let now = Instant::now();
+
+        for v in 0..MAX_SAMPLES as u32 + 10 {
+            rs.push(v, now);
+        }
+
+        assert_eq!(rs.samples.len(), MAX_SAMPLES);
+        assert_eq!(rs.stats().unwrap().min, 10);
+    }
+}
@@ -40,40 +40,36 @@ impl Default for Text {
     }
 }
 
+/// Number of samples in which each bit of a register was set
 #[derive(Default, Debug)]
-pub struct BITS(pub [u8; 32]);
+pub struct BITS {
+    pub acc: [u32; 32],
+    /// number of successful reads
+    pub count: u32,
+}
 
 impl BITS {
     pub fn clear(&mut self) {
-        *self = Self([0u8; 32])
+        *self = Self::default()
     }
 
     pub fn acc(&mut self, reg: u32) {
-        *self += Self::from(reg)
-    }
+        for (i, a) in self.acc.iter_mut().enumerate() {
+            *a = a.saturating_add((reg >> i) & 0b1);
+        }
 
-    pub fn get(&self, index: usize) -> u8 {
-        unsafe { *self.0.get_unchecked(index) }
+        self.count = self.count.saturating_add(1);
     }
-}
-
-impl From<u32> for BITS {
-    fn from(val: u32) -> Self {
-        let mut out = [0u8; 32];
 
-        for (i, o) in out.iter_mut().enumerate() {
-            *o = ((val >> i) & 0b1) as u8;
-        }
-
-        Self(out)
+    pub fn get(&self, index: usize) -> u32 {
+        self.acc[index]
     }
-}
 
-impl std::ops::AddAssign for BITS {
-    fn add_assign(&mut self, other: Self) {
-        for (dst, src) in self.0.iter_mut().zip(other.0.iter()) {
-            *dst += src;
-        }
+    /// Percentage of the reads in which the bit was set
+    pub fn percent(&self, index: usize) -> u32 {
+        if self.count == 0 { return 0 }
+
+        (self.get(index) as u64 * 100 / self.count as u64) as u32
     }
 }
 
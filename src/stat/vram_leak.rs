@@ -0,0 +1,120 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::time::{Duration, Instant};
+
+/// min number of samples to fit the growth rate
+const MIN_SAMPLES: usize = 10;
+/// min growth over the window, KiB
+const MIN_GROWTH: u64 = 16 << 10;
+/// max ratio of samples where the memory decreased
+const MAX_DROP_RATIO: f64 = 0.1;
+/// max number of samples over the window per process,
+/// samples closer than `window / MAX_SAMPLES` to the previous one are dropped
+const MAX_SAMPLES: u32 = 256;
+
+/// Per-PID history of VRAM + GTT usage, flags processes whose memory rises steadily over `window`
+#[derive(Clone, Debug)]
+pub struct LeakDetector {
+    pub window: Duration,
+    history: HashMap<i32, VecDeque<(Instant, u64)>>,
+}
+
+impl LeakDetector {
+    pub fn new(window: Duration) -> Self {
+        Self {
+            window,
+            history: HashMap::new(),
+        }
+    }
+
+    /// `vram` and `gtt` in KiB
+    pub fn update(&mut self, pid: i32, vram: u64, gtt: u64, now: Instant) {
+        let samples = self.history.entry(pid).or_default();
+
+        if let Some((last_time, _)) = samples.back() {
+            if now.duration_since(*last_time) < self.window / MAX_SAMPLES { return }
+        }
+
+        samples.push_back((now, vram + gtt));
+
+        while let Some((time, _)) = samples.front() {
+            if now.duration_since(*time) <= self.window { break }
+            samples.pop_front();
+        }
+    }
+
+    /// Growth rate of the process in KiB/s, `None` unless the memory rises steadily over the whole window
+    pub fn growth_rate(&self, pid: i32) -> Option<f64> {
+        let samples = self.history.get(&pid)?;
+        let (first_time, first) = *samples.front()?;
+        let (last_time, last) = *samples.back()?;
+
+        if samples.len() < MIN_SAMPLES { return None }
+        // the history does not cover the window yet
+        if last_time.duration_since(first_time) < self.window - self.window / 10 { return None }
+        if last < first + MIN_GROWTH { return None }
+
+        let drops = samples.iter().zip(samples.iter().skip(1))
+            .filter(|((_, pre), (_, cur))| cur < pre)
+            .count();
+        if MAX_DROP_RATIO < drops as f64 / (samples.len() - 1) as f64 { return None }
+
+        let rate = fit_slope(samples, first_time);
+
+        (0.0 < rate).then_some(rate)
+    }
+
+    pub fn retain(&mut self, alive: &HashSet<i32>) {
+        self.history.retain(|pid, _| alive.contains(pid));
+    }
+}
+
+/// Least squares slope of memory usage over time, KiB/s
+fn fit_slope(samples: &VecDeque<(Instant, u64)>, base: Instant) -> f64 {
+    let n = samples.len() as f64;
+    let points = samples.iter().map(|(time, mem)| (time.duration_since(base).as_secs_f64(), *mem as f64));
+
+    let (sum_x, sum_y, sum_xy, sum_xx) = points.fold((0.0, 0.0, 0.0, 0.0), |(sx, sy, sxy, sxx), (x, y)| {
+        (sx + x, sy + y, sxy + x * y, sxx + x * x)
+    });
+    let denom = n * sum_xx - sum_x * sum_x;
+
+    if denom == 0.0 { return 0.0 }
+
+    (n * sum_xy - sum_x * sum_y) / denom
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn steady_growth() {
+        let mut leak = LeakDetector::new(Duration::from_secs(60));
+        let base = Instant::now();
+
+        for sec in 0..=60 {
+            leak.update(1, sec << 10, 0, base + Duration::from_secs(sec));
+            leak.update(2, 1 << 20, 0, base + Duration::from_secs(sec));
+        }
+
+        let rate = leak.growth_rate(1).unwrap();
+        assert!((rate - 1024.0).abs() < 1e-6);
+        assert_eq!(leak.growth_rate(2), None);
+    }
+
+    #[test]
+    fn history_is_capped() {
+        let window = Duration::from_secs(60);
+        let mut leak = LeakDetector::new(window);
+        let base = Instant::now();
+
+        // updated every 10 ms for 2 windows
+        for ms in 0..12_000u64 {
+            leak.update(1, ms, 0, base + Duration::from_millis(ms * 10));
+        }
+
+        let samples = &leak.history[&1];
+        assert!(samples.len() <= MAX_SAMPLES as usize + 1);
+        assert!(window - window / 10 <= samples.back().unwrap().0.duration_since(samples.front().unwrap().0));
+    }
+}
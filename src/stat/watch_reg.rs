@@ -0,0 +1,307 @@
+use super::{DeviceHandle, Opt, TopView, UsageBars, toggle_view};
+use serde_json::{json, Map, Value};
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
+
+/// max width of a multi-bit field, one histogram row per value
+const MAX_WIDTH: u32 = 4;
+
+/// User-defined MMIO status field, "<name>:<offset>:<bit>[:<width>]"
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct WatchReg {
+    pub name: String,
+    /// dword offset for `read_mm_registers`
+    pub offset: u32,
+    pub bit: u32,
+    pub width: u32,
+}
+
+impl WatchReg {
+    pub fn field(&self, reg: u32) -> usize {
+        ((reg >> self.bit) & ((1 << self.width) - 1)) as usize
+    }
+
+    /// number of the values of the field
+    pub fn num_values(&self) -> usize {
+        1 << self.width
+    }
+}
+
+impl FromStr for WatchReg {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = || format!("invalid register: {s:?} (expected \"<name>:<offset>:<bit>[:<width>]\")");
+        let parts: Vec<&str> = s.split(':').collect();
+
+        let [name, offset, bit, width @ ..] = parts.as_slice() else { return Err(err()) };
+        if name.is_empty() || 1 < width.len() { return Err(err()) }
+
+        let offset = match offset.strip_prefix("0x").or_else(|| offset.strip_prefix("0X")) {
+            Some(hex) => u32::from_str_radix(hex, 16),
+            None => offset.parse::<u32>(),
+        }.map_err(|_| err())?;
+        let bit = bit.parse::<u32>().map_err(|_| err())?;
+        let width = match width.first() {
+            Some(w) => w.parse::<u32>().map_err(|_| err())?,
+            None => 1,
+        };
+
+        if !(1..=MAX_WIDTH).contains(&width) {
+            return Err(format!("{s:?}: width {width} is out of range (1-{MAX_WIDTH})"));
+        }
+        if 32 < bit + width {
+            return Err(format!("{s:?}: bits {bit}-{} are out of range (0-31)", bit + width - 1));
+        }
+
+        Ok(Self { name: name.to_string(), offset, bit, width })
+    }
+}
+
+impl fmt::Display for WatchReg {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{:#X}:{}:{}", self.name, self.offset, self.bit, self.width)
+    }
+}
+
+/// Samples the watch list, single bits as busy percentages and multi-bit fields as value histograms
+pub struct WatchCounter {
+    pub regs: Vec<WatchReg>,
+    /// per register, the number of successful reads of each value
+    hist: Vec<Vec<u32>>,
+    /// (register, value) of each bar, one for a single bit, one per value for a multi-bit field
+    rows: Vec<(usize, usize)>,
+    bars: UsageBars,
+}
+
+impl Default for WatchCounter {
+    fn default() -> Self {
+        Self::new(Vec::new(), Duration::ZERO)
+    }
+}
+
+impl WatchCounter {
+    const TITLE: &str = "Watch";
+
+    pub fn new(regs: Vec<WatchReg>, stats_window: Duration) -> Self {
+        let hist = regs.iter().map(|r| vec![0; r.num_values()]).collect();
+        let mut rows = Vec::new();
+        let mut names = Vec::new();
+
+        for (i, reg) in regs.iter().enumerate() {
+            if reg.width == 1 {
+                rows.push((i, 1));
+                names.push(reg.name.clone());
+            } else {
+                for val in 0..reg.num_values() {
+                    rows.push((i, val));
+                    names.push(format!("{} = {val}", reg.name));
+                }
+            }
+        }
+
+        Self {
+            regs,
+            hist,
+            rows,
+            bars: UsageBars::new(names, stats_window),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.regs.is_empty()
+    }
+
+    /// Drops registers not allowed to be read by the driver
+    pub fn check_reg_offset(&mut self, amdgpu_dev: &DeviceHandle) {
+        self.retain(amdgpu_dev, true);
+    }
+
+    /// `check_reg_offset` without the messages, for the JSON output
+    pub fn retain_readable(&mut self, amdgpu_dev: &DeviceHandle) {
+        self.retain(amdgpu_dev, false);
+    }
+
+    fn retain(&mut self, amdgpu_dev: &DeviceHandle, verbose: bool) {
+        let window = self.bars.stats.first().map(|s| s.window).unwrap_or_default();
+        let regs = std::mem::take(&mut self.regs);
+        let regs = regs.into_iter().filter(|r| {
+            amdgpu_dev.read_mm_registers(r.offset).map_or_else(|err| {
+                if verbose {
+                    println!("{} ({:#X}) register is not allowed. ({err})", r.name, r.offset);
+                }
+                false
+            }, |_| true)
+        }).collect();
+
+        *self = Self::new(regs, window);
+    }
+
+    fn percent(&self, i: usize, val: usize) -> u32 {
+        let reads: u64 = self.hist[i].iter().map(|n| *n as u64).sum();
+        if reads == 0 { return 0 }
+
+        (self.hist[i][val] as u64 * 100 / reads) as u32
+    }
+
+    fn usage(&self) -> Vec<u32> {
+        self.rows.iter().map(|(i, val)| self.percent(*i, *val)).collect()
+    }
+
+    pub fn top_view(
+        &self,
+        visible: bool,
+    ) -> TopView {
+        self.bars.top_view(Self::TITLE, visible)
+    }
+
+    pub fn read_reg(&mut self, amdgpu_dev: &DeviceHandle) {
+        // registers sharing an offset are read once per sample
+        let mut cache: Vec<(u32, Option<u32>)> = Vec::new();
+
+        for i in 0..self.regs.len() {
+            let offset = self.regs[i].offset;
+            let out = match cache.iter().find(|(off, _)| *off == offset) {
+                Some((_, out)) => *out,
+                None => {
+                    let out = amdgpu_dev.read_mm_registers(offset).ok();
+                    cache.push((offset, out));
+                    out
+                },
+            };
+
+            self.acc(i, out);
+        }
+    }
+
+    /// Failed reads (`None`) are not counted, so they do not lower the percentages.
+    fn acc(&mut self, i: usize, out: Option<u32>) {
+        let Some(out) = out else { return };
+        let n = &mut self.hist[i][self.regs[i].field(out)];
+
+        *n = n.saturating_add(1);
+    }
+
+    pub fn set_value(&self) {
+        self.bars.set_value(&self.usage());
+    }
+
+    pub fn clear(&mut self) {
+        for hist in self.hist.iter_mut() {
+            hist.fill(0);
+        }
+    }
+
+    pub fn dump(&mut self) {
+        self.update_stats();
+        self.set_value();
+        self.clear();
+    }
+
+    /// Adds the usage of the current refresh period to the stats window
+    pub fn update_stats(&mut self) {
+        let usage = self.usage();
+        self.bars.update_stats(&usage);
+    }
+
+    pub fn json_value(&self) -> Value {
+        let mut m = Map::new();
+        let usage = self.usage();
+
+        for (i, reg) in self.regs.iter().enumerate() {
+            let mut rows = self.rows.iter().zip(usage.iter()).enumerate()
+                .filter(|(_, ((reg_i, _), _))| *reg_i == i)
+                .map(|(row, ((_, val), usage))| (row, *val, *usage));
+
+            let v = if reg.width == 1 {
+                let Some((row, _, usage)) = rows.next() else { continue };
+
+                self.bars.json_value(row, usage)
+            } else {
+                let hist: Map<String, Value> = rows
+                    .map(|(row, val, usage)| (val.to_string(), self.bars.json_value(row, usage)))
+                    .collect();
+
+                json!({
+                    "histogram": hist,
+                    "unit": "%",
+                })
+            };
+
+            m.insert(reg.name.clone(), v);
+        }
+
+        m.into()
+    }
+
+    pub fn cb(siv: &mut cursive::Cursive) {
+        {
+            let mut opt = siv.user_data::<Opt>().unwrap().lock().unwrap();
+            opt.watch_reg ^= true;
+        }
+
+        siv.call_on_name(Self::TITLE, toggle_view);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn counter(regs: &[&str]) -> WatchCounter {
+        let regs = regs.iter().map(|s| s.parse().unwrap()).collect();
+
+        WatchCounter::new(regs, Duration::from_secs(10))
+    }
+
+    #[test]
+    fn parse_watch_reg() {
+        let reg: WatchReg = "GUI_ACTIVE:0x2004:31".parse().unwrap();
+        assert_eq!(reg, WatchReg { name: "GUI_ACTIVE".to_string(), offset: 0x2004, bit: 31, width: 1 });
+        assert_eq!(reg.to_string(), "GUI_ACTIVE:0x2004:31:1");
+
+        let reg: WatchReg = "ME0PIPE:8196:28:2".parse().unwrap();
+        assert_eq!((reg.offset, reg.bit, reg.width), (0x2004, 28, 2));
+        assert_eq!(reg.field(0b10 << 28), 2);
+
+        for s in ["", "A", "A:0x2004", ":0x2004:1", "A:0xZZ:1", "A:0x2004:1:0", "A:0x2004:1:5", "A:0x2004:31:2", "A:1:2:3:4"] {
+            assert!(s.parse::<WatchReg>().is_err(), "{s}");
+        }
+    }
+
+    #[test]
+    fn failed_reads_are_not_counted() {
+        let mut w = counter(&["GUI_ACTIVE:0x2004:31"]);
+
+        w.acc(0, Some(1 << 31));
+        w.acc(0, None);
+        w.acc(0, None);
+        w.acc(0, Some(0));
+        assert_eq!(w.usage(), [50]);
+
+        w.clear();
+        w.acc(0, None);
+        assert_eq!(w.usage(), [0]);
+    }
+
+    #[test]
+    fn histogram_rows() {
+        let mut w = counter(&["BUSY:0x2004:31", "STATE:0x2004:0:2"]);
+
+        assert_eq!(w.rows, [(0, 1), (1, 0), (1, 1), (1, 2), (1, 3)]);
+        assert_eq!(w.bars.names[1], "STATE = 0");
+
+        for out in [0b00, 0b01, 0b01, 0b11 | (1 << 31)] {
+            w.acc(0, Some(out));
+            w.acc(1, Some(out));
+        }
+        assert_eq!(w.usage(), [25, 25, 50, 0, 25]);
+
+        w.update_stats();
+        let v = w.json_value();
+        assert_eq!(v["BUSY"]["usage"], 25);
+        assert_eq!(v["STATE"]["histogram"]["1"]["usage"], 50);
+        assert_eq!(v["STATE"]["histogram"]["1"]["stats"]["max"], 50);
+    }
+}